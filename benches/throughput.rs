@@ -0,0 +1,58 @@
+//! Counting throughput of the built binary on the test fixtures, each repeated up to a few megabytes.
+
+use std::fs;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::thread;
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+
+// size in bytes each fixture is scaled up to
+const INPUT_SIZE: usize = 16 << 20;
+
+fn scaled(fixture: &str) -> Vec<u8> {
+    let path = format!("{}/tests/{}", env!("CARGO_MANIFEST_DIR"), fixture);
+    let data = fs::read(&path).unwrap_or_else(|e| panic!("Unable to read {}: {}", path, e));
+    data.repeat(INPUT_SIZE / data.len() + 1)
+}
+
+fn run(input: &[u8], args: &[&str]) {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_distribution"))
+        .arg("--rcfile=/dev/null")
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .expect("Unable to run distribution");
+    child.stdin.take().unwrap().write_all(input).unwrap();
+    assert!(child.wait().unwrap().success());
+}
+
+fn throughput(c: &mut Criterion) {
+    let cases: [(&str, &str, &[&str]); 5] = [
+        ("lines (01)", "stdin.01.txt", &[]),
+        ("lines (02)", "stdin.02.txt", &[]),
+        ("tokenize=word (02)", "stdin.02.txt", &["--tokenize=word", "--match=word"]),
+        ("tokenize=/ (03)", "stdin.03.txt", &["--tokenize=/"]),
+        ("tokenize=white (04)", "stdin.04.txt", &["--tokenize=white"]),
+    ];
+    let mut threads = vec![1, thread::available_parallelism().map(|n| n.get()).unwrap_or(1)];
+    threads.dedup();
+
+    let mut group = c.benchmark_group("throughput");
+    group.sample_size(10);
+    for (name, fixture, args) in cases {
+        let input = scaled(fixture);
+        group.throughput(Throughput::Bytes(input.len() as u64));
+        for &t in &threads {
+            let threads_arg = format!("--threads={}", t);
+            let args: Vec<&str> = args.iter().copied().chain([threads_arg.as_str()]).collect();
+            group.bench_function(format!("{} threads={}", name, t), |b| b.iter(|| run(&input, &args)));
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, throughput);
+criterion_main!(benches);
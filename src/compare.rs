@@ -0,0 +1,135 @@
+use std::cmp::Ordering;
+
+use super::pairlist::Pair;
+use super::tokenizer::FxIndexMap;
+
+/// A key's count in a baseline and in the current input.
+#[derive(Debug, PartialEq)]
+pub struct Delta {
+    key: String,
+    before: u64,
+    after: u64,
+}
+
+impl Delta {
+    pub fn new(key: &str, before: u64, after: u64) -> Delta {
+        Delta {
+            key: key.to_string(),
+            before,
+            after,
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Count in the baseline.
+    pub fn before(&self) -> u64 {
+        self.before
+    }
+
+    /// Count in the current input.
+    pub fn after(&self) -> u64 {
+        self.after
+    }
+
+    pub fn change(&self) -> i64 {
+        self.after as i64 - self.before as i64
+    }
+
+    /// Change as a percentage of the baseline, or `None` for keys new to the current input.
+    pub fn pct_change(&self) -> Option<f64> {
+        if self.before == 0 {
+            None
+        } else {
+            Some(self.change() as f64 / self.before as f64 * 100.0)
+        }
+    }
+
+    /// Largest current count first, then largest baseline count.
+    pub fn cmp_count(&self, other: &Delta) -> Ordering {
+        other
+            .after
+            .cmp(&self.after)
+            .then(other.before.cmp(&self.before))
+            .then(self.key.cmp(&other.key))
+    }
+
+    /// Largest change in either direction first.
+    pub fn cmp_change(&self, other: &Delta) -> Ordering {
+        other
+            .change()
+            .unsigned_abs()
+            .cmp(&self.change().unsigned_abs())
+            .then(other.change().cmp(&self.change()))
+            .then(self.key.cmp(&other.key))
+    }
+}
+
+/// Pairs up the keys of a baseline and the current input, treating keys missing from either as 0.
+///
+/// Keys are returned in the order of the baseline, followed by those new to the current input.
+pub fn compare(before: Vec<Pair>, after: Vec<Pair>) -> Vec<Delta> {
+    let mut deltas: FxIndexMap<String, Delta> = FxIndexMap::default();
+    for p in before {
+        deltas.insert(p.key().to_string(), Delta::new(p.key(), p.value(), 0));
+    }
+    for p in after {
+        deltas.entry(p.key().to_string()).or_insert_with(|| Delta::new(p.key(), 0, 0)).after = p.value();
+    }
+    deltas.into_values().collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{compare, Delta};
+    use crate::pairlist::Pair;
+
+    #[test]
+    fn changes() {
+        let d = Delta::new("a", 100, 120);
+        assert_eq!(d.change(), 20);
+        assert_eq!(d.pct_change(), Some(20.0));
+
+        let d = Delta::new("b", 4, 0);
+        assert_eq!(d.change(), -4);
+        assert_eq!(d.pct_change(), Some(-100.0));
+
+        assert_eq!(Delta::new("c", 0, 3).pct_change(), None);
+    }
+
+    #[test]
+    fn compare_keys() {
+        let before = vec![Pair::new(10, "a"), Pair::new(5, "b")];
+        let after = vec![Pair::new(12, "a"), Pair::new(1, "c")];
+
+        let mut deltas = compare(before, after);
+        deltas.sort_by(Delta::cmp_count);
+
+        assert_eq!(deltas, [Delta::new("a", 10, 12), Delta::new("c", 0, 1), Delta::new("b", 5, 0)]);
+    }
+
+    #[test]
+    fn compare_keeps_input_order() {
+        let before = vec![Pair::new(1, "z"), Pair::new(5, "b")];
+        let after = vec![Pair::new(1, "c"), Pair::new(2, "b"), Pair::new(3, "a")];
+
+        let keys: Vec<String> = compare(before, after).iter().map(|d| d.key().to_string()).collect();
+        assert_eq!(keys, ["z", "b", "c", "a"]);
+    }
+
+    #[test]
+    fn sort_by_change() {
+        let mut deltas = [
+            Delta::new("a", 10, 12),
+            Delta::new("b", 5, 0),
+            Delta::new("c", 0, 5),
+            Delta::new("d", 1, 1),
+        ];
+        deltas.sort_by(Delta::cmp_change);
+
+        let keys: Vec<&str> = deltas.iter().map(Delta::key).collect();
+        assert_eq!(keys, ["c", "b", "a", "d"]);
+    }
+}
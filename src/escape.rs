@@ -0,0 +1,204 @@
+use std::borrow::Cow;
+use std::fmt::Write;
+
+/// Makes a key safe to print to a terminal by replacing control characters with visible escapes.
+///
+/// Keys come straight from the input, so escape sequences in them could move the cursor, recolour
+/// or clear the screen, and tabs or carriage returns would break the alignment of the columns.
+/// Bidirectional overrides are escaped too, as they can make a key display as a different one, and
+/// so are backslashes, so that a key can't spell out the escape of another.
+pub fn escape(key: &str) -> Cow<'_, str> {
+    if !key.chars().any(needs_escape) {
+        return Cow::Borrowed(key);
+    }
+
+    let mut escaped = String::with_capacity(key.len() + 8);
+    for c in key.chars() {
+        match c {
+            '\t' => escaped.push_str("\\t"),
+            '\r' => escaped.push_str("\\r"),
+            '\n' => escaped.push_str("\\n"),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_ascii_control() => write!(escaped, "\\x{:02x}", c as u32).unwrap(),
+            c if needs_escape(c) => write!(escaped, "\\u{{{:x}}}", c as u32).unwrap(),
+            c => escaped.push(c),
+        }
+    }
+    Cow::Owned(escaped)
+}
+
+/// Quotes `s` as a JSON string.
+pub fn json_string(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if (c as u32) < 0x20 => write!(quoted, "\\u{:04x}", c as u32).unwrap(),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Quotes `s` as a CSV field when it contains a comma, quote or line break, doubling any quotes.
+pub fn csv_field(s: &str) -> Cow<'_, str> {
+    if !s.contains([',', '"', '\r', '\n']) {
+        return Cow::Borrowed(s);
+    }
+    Cow::Owned(format!("\"{}\"", s.replace('"', "\"\"")))
+}
+
+/// Escapes tabs, line breaks and backslashes in `s` so it fits in one TSV field.
+pub fn tsv_field(s: &str) -> Cow<'_, str> {
+    if !s.contains(['\t', '\r', '\n', '\\']) {
+        return Cow::Borrowed(s);
+    }
+    let mut escaped = String::with_capacity(s.len() + 4);
+    for c in s.chars() {
+        match c {
+            '\t' => escaped.push_str("\\t"),
+            '\r' => escaped.push_str("\\r"),
+            '\n' => escaped.push_str("\\n"),
+            '\\' => escaped.push_str("\\\\"),
+            c => escaped.push(c),
+        }
+    }
+    Cow::Owned(escaped)
+}
+
+/// Escapes the pipes and line breaks in `s`, which would otherwise end its markdown table cell or
+/// row, and the backslashes that would otherwise escape them.
+pub fn markdown_cell(s: &str) -> Cow<'_, str> {
+    if !s.contains(['|', '\\', '\r', '\n']) {
+        return Cow::Borrowed(s);
+    }
+    let mut escaped = String::with_capacity(s.len() + 4);
+    for c in s.chars() {
+        match c {
+            '|' => escaped.push_str("\\|"),
+            '\\' => escaped.push_str("\\\\"),
+            '\r' => escaped.push_str("\\r"),
+            '\n' => escaped.push_str("\\n"),
+            c => escaped.push(c),
+        }
+    }
+    Cow::Owned(escaped)
+}
+
+/// Escapes the characters of `s` that are markup in XML, for SVG and HTML text and attributes.
+pub fn xml_text(s: &str) -> Cow<'_, str> {
+    if !s.contains(['&', '<', '>', '"', '\'']) {
+        return Cow::Borrowed(s);
+    }
+    let mut escaped = String::with_capacity(s.len() + 8);
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    Cow::Owned(escaped)
+}
+
+/// Escapes backslashes, quotes and newlines in `s` for an OpenMetrics label value.
+pub fn label_value(s: &str) -> Cow<'_, str> {
+    if !s.contains(['\\', '"', '\n']) {
+        return Cow::Borrowed(s);
+    }
+    Cow::Owned(s.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n"))
+}
+
+fn needs_escape(c: char) -> bool {
+    c.is_control() || matches!(c, '\\' | '\u{200e}' | '\u{200f}' | '\u{202a}'..='\u{202e}' | '\u{2066}'..='\u{2069}')
+}
+
+#[cfg(test)]
+mod test {
+    use super::{csv_field, escape, json_string, label_value, markdown_cell, tsv_field, xml_text};
+    use std::borrow::Cow;
+
+    #[test]
+    fn plain_keys_are_borrowed() {
+        assert!(matches!(escape("GET /index.html"), Cow::Borrowed(_)));
+        assert!(matches!(escape("日本語 café"), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn whitespace() {
+        assert_eq!(escape("a\tb\r\n"), "a\\tb\\r\\n");
+    }
+
+    #[test]
+    fn ansi_sequences() {
+        assert_eq!(escape("\u{1b}[31mred\u{1b}[0m"), "\\x1b[31mred\\x1b[0m");
+        assert_eq!(escape("bell\u{7}\u{7f}"), "bell\\x07\\x7f");
+    }
+
+    #[test]
+    fn backslashes() {
+        assert_eq!(escape("a\\x1bb"), "a\\\\x1bb");
+        assert_ne!(escape("a\\x1bb"), escape("a\u{1b}b"));
+    }
+
+    #[test]
+    fn unicode_controls() {
+        assert_eq!(escape("\u{9b}2J"), "\\u{9b}2J");
+        assert_eq!(escape("abc\u{202e}gpj.exe"), "abc\\u{202e}gpj.exe");
+    }
+
+    #[test]
+    fn json_strings() {
+        assert_eq!(json_string("plain"), "\"plain\"");
+        assert_eq!(json_string("say \"hi\"\\"), "\"say \\\"hi\\\"\\\\\"");
+        assert_eq!(json_string("a\tb\n\u{1b}[0m"), "\"a\\tb\\n\\u001b[0m\"");
+        assert_eq!(json_string("日本"), "\"日本\"");
+    }
+
+    #[test]
+    fn csv_fields() {
+        assert!(matches!(csv_field("GET /a b"), Cow::Borrowed(_)));
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+    }
+
+    #[test]
+    fn tsv_fields() {
+        assert!(matches!(tsv_field("a,b \"c\""), Cow::Borrowed(_)));
+        assert_eq!(tsv_field("a\tb\r\n"), "a\\tb\\r\\n");
+        assert_eq!(tsv_field("C:\\dir"), "C:\\\\dir");
+    }
+
+    #[test]
+    fn markdown_cells() {
+        assert!(matches!(markdown_cell("a,b"), Cow::Borrowed(_)));
+        assert_eq!(markdown_cell("a|b||c"), "a\\|b\\|\\|c");
+        assert_eq!(markdown_cell("a\\|b"), "a\\\\\\|b");
+        assert_eq!(markdown_cell("a\r\nb"), "a\\r\\nb");
+    }
+
+    #[test]
+    fn xml() {
+        assert!(matches!(xml_text("GET /index.html"), Cow::Borrowed(_)));
+        assert_eq!(
+            xml_text("<a href=\"x\">Tom & Jerry's</a>"),
+            "&lt;a href=&quot;x&quot;&gt;Tom &amp; Jerry&#39;s&lt;/a&gt;"
+        );
+    }
+
+    #[test]
+    fn label_values() {
+        assert!(matches!(label_value("GET /a\tb"), Cow::Borrowed(_)));
+        assert_eq!(label_value("C:\\ \"x\"\n"), "C:\\\\ \\\"x\\\"\\n");
+    }
+}
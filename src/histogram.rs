@@ -1,15 +1,62 @@
+use std::borrow::Cow;
 use std::cmp;
-use std::io;
+use std::io::{self, Write};
 
-use super::pairlist::Pair;
-use super::settings::Settings;
+use super::compare::Delta;
+use super::escape::{csv_field, escape, json_string, label_value, markdown_cell, tsv_field, xml_text};
+use super::numeric::format_number;
+use super::pairlist::{natural_cmp, Pair};
+use super::settings::{MetricType, Orientation, OutputFormat, Settings, SortOrder, HORIZONTAL_BLOCKS, VERTICAL_BLOCKS};
+use super::stacked::{Breakdown, MAX_CATEGORIES};
+use super::statistics::Statistics;
+use super::summary::Summary;
+use super::truncate::truncate;
+use super::width::{display_width, grapheme_width, pad_left};
 
 use log::debug;
+use unicode_segmentation::UnicodeSegmentation;
+
+// each segment of a stacked bar is drawn with its own character, and colour with --color
+const STACK_CHARS: [char; MAX_CATEGORIES] = ['#', '=', '*', '+', '%', 'o', ':', '.'];
+const STACK_COLOURS: [u8; MAX_CATEGORIES] = [32, 33, 34, 35, 36, 31, 92, 37];
+
+// keys are never shortened below this to make room for the bars
+const MIN_KEY_WIDTH: usize = 4;
+
+// baseline bars of --compare are drawn with this instead of the histogram character
+const BASELINE_CHAR: char = '=';
+
+// the bars of markdown tables take this fraction of --width, as the other columns are laid out by
+// whatever renders the table rather than padded to fit
+const MARKDOWN_BAR_SHARE: usize = 4;
+
+// most rows the keys beneath vertical columns are written down, without --key-width
+const MAX_LABEL_ROWS: usize = 10;
+
+// size in pixels of a column and a row of SVG charts, which are laid out like the terminal output
+const CHART_COLUMN: usize = 8;
+const CHART_ROW: usize = 20;
+const CHART_MARGIN: usize = 10;
 
 pub struct HistogramWriter {
     settings: Settings,
     height: usize,
     width: usize,
+    // estimated count of distinct keys, when --approx kept only some of them
+    distinct: Option<u64>,
+}
+
+/// The rows of a histogram to show, once sorted and narrowed down to a window.
+struct Selection<'a> {
+    rows: Vec<&'a Pair>,
+    // rank of the first row, counting from 1
+    first_rank: usize,
+    // total count of the rows ranked above the window, where the cumulative percentage starts
+    above: u64,
+    total: u64,
+    // everything not shown, added up into one row
+    other: Pair,
+    hidden: usize,
 }
 
 #[derive(Debug)]
@@ -17,6 +64,8 @@ struct ColumnWidths {
     key: usize,
     token: usize,
     pct: usize,
+    // zero without --cumulative
+    cum: usize,
 }
 
 impl HistogramWriter {
@@ -27,6 +76,29 @@ impl HistogramWriter {
             settings,
             width: w,
             height: h,
+            distinct: None,
+        }
+    }
+
+    /// Reports the keys of an approximate tally by this estimate of the distinct keys in the input,
+    /// rather than by the number of keys kept.
+    pub fn with_distinct_estimate(self, distinct: u64) -> HistogramWriter {
+        HistogramWriter {
+            distinct: Some(distinct),
+            ..self
+        }
+    }
+
+    // keys in the input, given the `kept` keys of the tally
+    fn input_keys(&self, kept: usize) -> u64 {
+        self.distinct.unwrap_or(kept as u64)
+    }
+
+    // a number of keys, marked with ~ when it is estimated
+    fn keys_label(&self, keys: u64) -> String {
+        match self.distinct {
+            Some(_) => format!("~{}", HistogramWriter::with_commas(keys)),
+            None => HistogramWriter::with_commas(keys),
         }
     }
 
@@ -34,6 +106,9 @@ impl HistogramWriter {
         write!(w, "{:>width$}", "Key", width = col_widths.key)?;
         write!(w, "|{:>width$}", "Ct", width = col_widths.token)?;
         write!(w, " {:>width$}", "(Pct)", width = col_widths.pct)?;
+        if col_widths.cum > 0 {
+            write!(w, " {:>width$}", "(Cum)", width = col_widths.cum)?;
+        }
         writeln!(w, " Histogram")?;
         writeln!(
             w,
@@ -45,27 +120,634 @@ impl HistogramWriter {
         Ok(())
     }
 
+    pub fn write_summary<W: io::Write>(&self, w: &mut W, summary: &Summary) -> io::Result<()> {
+        writeln!(w, "tokens/lines examined: {}", HistogramWriter::with_commas(summary.examined()))?;
+        writeln!(w, " tokens/lines matched: {}", HistogramWriter::with_commas(summary.matched()))?;
+        writeln!(w, "       histogram keys: {}", HistogramWriter::with_commas(summary.keys()))?;
+        // without --approx every distinct key is in the histogram
+        if summary.distinct_estimated() {
+            writeln!(w, " distinct keys (est.): ~{}", HistogramWriter::with_commas(summary.distinct()))?;
+        }
+        let millis = format!("{:.2}", summary.runtime().as_secs_f64() * 1000.0);
+        let (whole, fraction) = millis.split_at(millis.find('.').expect("runtime has no fraction"));
+        let whole = HistogramWriter::with_commas(whole.parse().expect("runtime is not a number"));
+        writeln!(w, "              runtime: {}{}ms", whole, fraction)?;
+
+        Ok(())
+    }
+
+    pub fn write_statistics<W: io::Write>(&self, w: &mut W, stats: &Statistics) -> io::Result<()> {
+        write!(w, "count: {}", HistogramWriter::with_commas(stats.count()))?;
+        write!(w, "  min: {}", format_number(stats.min()))?;
+        write!(w, "  max: {}", format_number(stats.max()))?;
+        write!(w, "  mean: {}", format_number(stats.mean()))?;
+        write!(w, "  stddev: {}", format_number(stats.stddev()))?;
+        writeln!(w)?;
+
+        // estimated percentiles are marked like approximate counts
+        let approx = if stats.exact() { "" } else { "~" };
+        write!(w, "median: {}{}", approx, format_number(stats.quantile(0.5)))?;
+        for (label, q) in [("p90", 0.9), ("p95", 0.95), ("p99", 0.99), ("p99.9", 0.999)] {
+            write!(w, "  {}: {}{}", label, approx, format_number(stats.quantile(q)))?;
+        }
+        writeln!(w)?;
+
+        Ok(())
+    }
+
+    fn with_commas(n: u64) -> String {
+        let digits = n.to_string();
+        let mut s = String::new();
+        for (i, c) in digits.chars().enumerate() {
+            if i > 0 && (digits.len() - i).is_multiple_of(3) {
+                s.push(',');
+            }
+            s.push(c);
+        }
+        s
+    }
+
     fn pct_width(max: u64, total: u64) -> usize {
         format!("({:2.2}%)", 100.0 * max as f64 / total as f64).len()
     }
 
-    pub fn write_histogram<T: io::Write>(&self, writer: &mut T, pairlist: &mut Vec<Pair>) -> io::Result<()> {
-        let output_limit = cmp::min(self.height, pairlist.len());
-        pairlist.sort_by(|a, b| b.cmp(a));
-        let data: Vec<_> = pairlist.iter().take(output_limit).collect();
+    // no count is more than the total, so this is room enough to write any of them
+    fn count_width(pairlist: &[Pair]) -> usize {
+        pairlist.iter().fold(0, |sum, p| sum + p.value()).to_string().len()
+    }
+
+    pub fn write_histogram<T: io::Write>(&self, writer: &mut T, pairlist: &mut [Pair]) -> io::Result<()> {
+        match self.settings.orientation() {
+            Orientation::Horizontal => self.write_rows(writer, pairlist, None),
+            Orientation::Vertical => self.write_columns(writer, pairlist),
+        }
+    }
+
+    /// Draws each bar as segments, one per secondary key in `breakdown`.
+    pub fn write_stacked_histogram<T: io::Write>(&self, writer: &mut T, pairlist: &mut [Pair], breakdown: &Breakdown) -> io::Result<()> {
+        self.write_rows(writer, pairlist, Some(breakdown))
+    }
+
+    /// Shows which character (and colour) stands for each secondary key of a stacked histogram.
+    pub fn write_legend<W: io::Write>(&self, w: &mut W, breakdown: &Breakdown) -> io::Result<()> {
+        write!(w, "Legend:")?;
+        for (i, category) in breakdown.categories().iter().enumerate() {
+            write!(
+                w,
+                " {}{}{} {}",
+                self.stack_colour(i),
+                STACK_CHARS[i],
+                self.settings.regular_colour(),
+                self.escape(category)
+            )?;
+        }
+        writeln!(w)?;
+
+        Ok(())
+    }
+
+    /// Draws a pair of bars for each key, the baseline above the current input, with the change
+    /// between them.
+    pub fn write_comparison<T: io::Write>(&self, writer: &mut T, deltas: &mut [Delta]) -> io::Result<()> {
+        match self.settings.sort_order() {
+            SortOrder::Count => deltas.sort_by(Delta::cmp_count),
+            SortOrder::CountAscending => deltas.sort_by(|a, b| {
+                a.after()
+                    .cmp(&b.after())
+                    .then(a.before().cmp(&b.before()))
+                    .then(a.key().cmp(b.key()))
+            }),
+            SortOrder::Change => deltas.sort_by(Delta::cmp_change),
+            SortOrder::Key => deltas.sort_by(|a, b| a.key().cmp(b.key())),
+            SortOrder::Natural => deltas.sort_by(|a, b| natural_cmp(a.key(), b.key())),
+            SortOrder::Input => {}
+        }
+        let deltas: &[Delta] = deltas;
+        let total = deltas.iter().fold(0, |sum, d| sum + d.after());
+        let selected: Vec<&Delta> = deltas.iter().filter(|d| self.in_range(d.after())).collect();
+        let counts: Vec<u64> = selected.iter().map(|d| d.after()).collect();
+        let (start, end) = self.window(&counts, total, self.height);
+        let mut data = selected[start..end].to_vec();
+
+        let hidden = deltas.len() - data.len();
+        let before = deltas.iter().fold(0, |sum, d| sum + d.before()) - data.iter().fold(0, |sum, d| sum + d.before());
+        let after = total - data.iter().fold(0, |sum, d| sum + d.after());
+        let other = Delta::new(&format!("(other {} keys)", hidden), before, after);
+        if self.settings.other() && hidden > 0 {
+            data.push(&other);
+        }
+
+        let changes: Vec<(String, String)> = data
+            .iter()
+            .map(|d| {
+                let pct = match d.pct_change() {
+                    Some(pct) => format!("({:+.2}%)", pct),
+                    None => String::from("(new)"),
+                };
+                (format!("{:+}", d.change()), pct)
+            })
+            .collect();
+
+        let max_value = data.iter().fold(0, |max, d| cmp::max(max, cmp::max(d.before(), d.after())));
+        let token_width = cmp::max(2, format!("{}", max_value).len());
+        let delta_width = changes.iter().fold(5, |max, c| cmp::max(max, c.0.len()));
+        let pct_width = changes.iter().fold(8, |max, c| cmp::max(max, c.1.len()));
+        let longest_key = data.iter().fold(3, |max, d| cmp::max(max, display_width(&self.escape(d.key()))));
+        let key_width = self.key_column_width(longest_key, 1 + token_width + 1 + delta_width + 1 + pct_width + 1 + 1);
+        let content_width = key_width + 1 + token_width + 1 + delta_width + 1 + pct_width + 1 + 1;
+        let bar_width = self.width.saturating_sub(content_width);
+
+        let mut stderr = io::stderr();
+        write!(stderr, "{:>width$}", "Key", width = key_width)?;
+        write!(stderr, "|{:>width$}", "Ct", width = token_width)?;
+        write!(stderr, " {:>width$}", "Delta", width = delta_width)?;
+        writeln!(stderr, " {:>width$} Histogram", "(Change)", width = pct_width)?;
+        writeln!(
+            stderr,
+            "{}|{}",
+            "-".repeat(key_width),
+            "-".repeat(self.width.saturating_sub(key_width + 1))
+        )?;
+
+        for (i, (d, (delta, pct))) in data.iter().zip(changes).enumerate() {
+            let baseline: String = self
+                .histogram_bar(max_value, bar_width, d.before())
+                .chars()
+                .map(|_| BASELINE_CHAR)
+                .collect();
+            write!(writer, "{}", pad_left(&self.shorten(d.key(), key_width), key_width))?;
+            write!(writer, "{}|", self.settings.regular_colour())?;
+            write!(writer, "{}{:>width$}", self.settings.ct_colour(), d.before(), width = token_width)?;
+            write!(writer, " {:>width$}", "", width = delta_width + 1 + pct_width)?;
+            writeln!(
+                writer,
+                "{} {}{}",
+                self.settings.graph_colour(),
+                baseline,
+                self.settings.key_colour()
+            )?;
+
+            write!(writer, "{:>width$}", "", width = key_width)?;
+            write!(writer, "{}|", self.settings.regular_colour())?;
+            write!(writer, "{}{:>width$}", self.settings.ct_colour(), d.after(), width = token_width)?;
+            write!(writer, "{} {:>width$}", self.settings.pct_colour(), delta, width = delta_width)?;
+            write!(writer, " {:>width$}", pct, width = pct_width)?;
+            write!(
+                writer,
+                "{} {}",
+                self.settings.graph_colour(),
+                self.histogram_bar(max_value, bar_width, d.after())
+            )?;
+
+            if i == data.len() - 1 {
+                writeln!(writer, "{}", self.settings.regular_colour())?;
+            } else {
+                writeln!(writer, "{}", self.settings.key_colour())?;
+            }
+        }
+        if self.settings.totals() {
+            self.write_totals(&mut stderr, total, deltas.len())?;
+        }
+        Ok(())
+    }
+
+    /// Writes the rows, totals, summary and any statistics as one JSON document, with keys as they
+    /// are rather than escaped for the terminal.
+    pub fn write_json<T: io::Write>(
+        &self,
+        writer: &mut T,
+        pairlist: &mut [Pair],
+        breakdown: Option<&Breakdown>,
+        summary: &Summary,
+        stats: Option<&Statistics>,
+    ) -> io::Result<()> {
+        self.sort(pairlist);
+        let selection = self.select(pairlist);
+        let total = selection.total;
+        let pct = |value: u64| format_number(value as f64 / total as f64 * 100.0);
+
+        writeln!(writer, "{{")?;
+        writeln!(writer, "  \"rows\": [")?;
+        let mut cumulative = selection.above;
+        for (i, p) in selection.rows.iter().enumerate() {
+            cumulative += p.value();
+            write!(
+                writer,
+                "    {{\"rank\": {}, \"key\": {}",
+                selection.first_rank + i,
+                json_string(p.key())
+            )?;
+            write!(writer, ", \"count\": {}, \"percent\": {}", p.value(), pct(p.value()))?;
+            if self.settings.cumulative() {
+                write!(writer, ", \"cumulative_percent\": {}", pct(cumulative))?;
+            }
+            if p.error() > 0 {
+                write!(writer, ", \"error\": {}", p.error())?;
+            }
+            if let Some(b) = breakdown {
+                let segments: Vec<String> = b
+                    .categories()
+                    .iter()
+                    .zip(b.segments(p.key()))
+                    .filter(|(_, &n)| n > 0)
+                    .map(|(category, n)| format!("{}: {}", json_string(category), n))
+                    .collect();
+                write!(writer, ", \"breakdown\": {{{}}}", segments.join(", "))?;
+            }
+            let comma = if i + 1 < selection.rows.len() { "," } else { "" };
+            writeln!(writer, "}}{}", comma)?;
+        }
+        writeln!(writer, "  ],")?;
+
+        if self.settings.other() && selection.hidden > 0 {
+            let other = &selection.other;
+            write!(
+                writer,
+                "  \"other\": {{\"keys\": {}, \"count\": {}",
+                self.input_keys(pairlist.len()).saturating_sub(selection.rows.len() as u64),
+                other.value()
+            )?;
+            writeln!(writer, ", \"percent\": {}}},", pct(other.value()))?;
+        }
+        writeln!(writer, "  \"total\": {},", total)?;
+        writeln!(writer, "  \"keys\": {},", self.input_keys(pairlist.len()))?;
+        writeln!(writer, "  \"keys_estimated\": {},", self.distinct.is_some())?;
+
+        write!(
+            writer,
+            "  \"summary\": {{\"examined\": {}, \"matched\": {}",
+            summary.examined(),
+            summary.matched()
+        )?;
+        write!(
+            writer,
+            ", \"histogram_keys\": {}, \"distinct_keys\": {}",
+            summary.keys(),
+            summary.distinct()
+        )?;
+        write!(writer, ", \"distinct_estimated\": {}", summary.distinct_estimated())?;
+        write!(writer, ", \"runtime_ms\": {:.2}}}", summary.runtime().as_secs_f64() * 1000.0)?;
+
+        if let Some(stats) = stats {
+            writeln!(writer, ",")?;
+            write!(writer, "  \"statistics\": {{\"count\": {}", stats.count())?;
+            for (label, value) in [
+                ("min", stats.min()),
+                ("max", stats.max()),
+                ("mean", stats.mean()),
+                ("stddev", stats.stddev()),
+            ] {
+                write!(writer, ", \"{}\": {}", label, format_number(value))?;
+            }
+            for (label, q) in [("median", 0.5), ("p90", 0.9), ("p95", 0.95), ("p99", 0.99), ("p99.9", 0.999)] {
+                write!(writer, ", \"{}\": {}", label, format_number(stats.quantile(q)))?;
+            }
+            write!(writer, ", \"exact\": {}}}", stats.exact())?;
+        }
+        writeln!(writer)?;
+        writeln!(writer, "}}")?;
+
+        Ok(())
+    }
+
+    /// Writes the rows as CSV or TSV, per `--output`.
+    pub fn write_delimited<T: io::Write>(&self, writer: &mut T, pairlist: &mut [Pair]) -> io::Result<()> {
+        let (separator, field): (&str, fn(&str) -> Cow<'_, str>) = match self.settings.output_format() {
+            OutputFormat::Tsv => ("\t", tsv_field),
+            _ => (",", csv_field),
+        };
+        let cumulative_column = self.settings.cumulative();
+        // flags the remainder row, since its label could also be a real key
+        let other_column = self.settings.other();
+
+        if self.settings.header() {
+            let mut columns = vec!["key", "count", "percent"];
+            if cumulative_column {
+                columns.push("cumulative_percent");
+            }
+            if other_column {
+                columns.push("other");
+            }
+            writeln!(writer, "{}", columns.join(separator))?;
+        }
+
+        self.sort(pairlist);
+        let selection = self.select(pairlist);
+        let total = selection.total;
+        let mut data = selection.rows.clone();
+        if self.settings.other() && selection.hidden > 0 {
+            data.push(&selection.other);
+        }
+
+        let mut cumulative = selection.above;
+        for p in data {
+            cumulative += p.value();
+            let pct = format_number(p.value() as f64 / total as f64 * 100.0);
+            write!(writer, "{}{}{}{}{}", field(p.key()), separator, p.value(), separator, pct)?;
+            if cumulative_column {
+                write!(writer, "{}{}", separator, format_number(cumulative as f64 / total as f64 * 100.0))?;
+            }
+            if other_column {
+                write!(writer, "{}{}", separator, std::ptr::eq(p, &selection.other))?;
+            }
+            writeln!(writer)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the rows as a GitHub-flavoured markdown table, with bars drawn in eighths of a block.
+    pub fn write_markdown<T: io::Write>(&self, writer: &mut T, pairlist: &mut [Pair]) -> io::Result<()> {
+        self.sort(pairlist);
+        let selection = self.select(pairlist);
+        let total = selection.total;
+        let mut data = selection.rows.clone();
+        if self.settings.other() && selection.hidden > 0 {
+            data.push(&selection.other);
+        }
+        let max_value = data.iter().fold(0, |max, p| cmp::max(max, p.value()));
+        let bar_width = cmp::max(self.width / MARKDOWN_BAR_SHARE, 1);
+
+        if self.settings.cumulative() {
+            writeln!(writer, "| Key | Count | Percent | Cumulative | |")?;
+            writeln!(writer, "|--:|--:|--:|--:|:--|")?;
+        } else {
+            writeln!(writer, "| Key | Count | Percent | |")?;
+            writeln!(writer, "|--:|--:|--:|:--|")?;
+        }
+
+        let mut cumulative = selection.above;
+        for p in data {
+            cumulative += p.value();
+            let count = if p.error() > 0 {
+                format!("~{}", p.value())
+            } else {
+                p.value().to_string()
+            };
+            write!(writer, "| {} | {} ", markdown_cell(&self.escape(p.key())), count)?;
+            write!(writer, "| {:.2}% ", p.value() as f64 / total as f64 * 100.0)?;
+            if self.settings.cumulative() {
+                write!(writer, "| {:.2}% ", cumulative as f64 / total as f64 * 100.0)?;
+            }
+            writeln!(writer, "| {} |", HistogramWriter::block_bar(max_value, bar_width, p.value()))?;
+        }
+        Ok(())
+    }
+
+    /// Writes the rows as a standalone SVG bar chart, `--width` columns wide and in the colours of
+    /// `--palette`.
+    pub fn write_svg<T: io::Write>(&self, writer: &mut T, pairlist: &mut [Pair]) -> io::Result<()> {
+        self.sort(pairlist);
+        let selection = self.select(pairlist);
+        let total = selection.total;
+        let mut data = selection.rows.clone();
+        if self.settings.other() && selection.hidden > 0 {
+            data.push(&selection.other);
+        }
+
+        let labels: Vec<(String, String)> = data
+            .iter()
+            .map(|p| {
+                let count = if p.error() > 0 {
+                    format!("~{}", p.value())
+                } else {
+                    p.value().to_string()
+                };
+                (count, format!("({:.2}%)", p.value() as f64 / total as f64 * 100.0))
+            })
+            .collect();
+        let max_value = data.iter().fold(0, |max, p| cmp::max(max, p.value()));
+        let longest_key = data.iter().fold(0, |max, p| cmp::max(max, display_width(&escape(p.key()))));
+        let longest_label = labels.iter().fold(0, |max, (c, p)| cmp::max(max, c.len() + 1 + p.len()));
+        let key_width = self.key_column_width(longest_key, 1 + longest_label + 1);
+        let bar_width = self.width.saturating_sub(key_width + 1 + longest_label + 1);
+
+        let palette = self.settings.palette();
+        let colour = |i: usize, default: &str| css_colour(&palette[i]).unwrap_or_else(|| default.to_string());
+        let (text, key, count, pct, bar) = (
+            colour(0, "#000000"),
+            colour(1, "#000000"),
+            colour(2, "#00a000"),
+            colour(3, "#a000a0"),
+            colour(4, "#0000c0"),
+        );
+
+        let title = self.settings.title().map(xml_text);
+        let top = CHART_MARGIN + if title.is_some() { CHART_ROW } else { 0 };
+        let width = self.width * CHART_COLUMN + 2 * CHART_MARGIN;
+        let height = top + data.len() * CHART_ROW + CHART_MARGIN;
+
+        write!(
+            writer,
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\"",
+            width, height
+        )?;
+        writeln!(writer, " font-family=\"monospace\" font-size=\"13\" fill=\"{}\">", text)?;
+        if let Some(title) = &title {
+            writeln!(writer, "<title>{}</title>", title)?;
+            write!(
+                writer,
+                "<text x=\"{}\" y=\"{}\" font-weight=\"bold\">",
+                CHART_MARGIN,
+                CHART_MARGIN + 14
+            )?;
+            writeln!(writer, "{}</text>", title)?;
+        }
+
+        let key_right = CHART_MARGIN + key_width * CHART_COLUMN;
+        let bar_left = key_right + CHART_COLUMN;
+        for (i, (p, (count_label, pct_label))) in data.iter().zip(&labels).enumerate() {
+            let y = top + i * CHART_ROW;
+            let length = (p.value() as f64 / max_value as f64 * (bar_width * CHART_COLUMN) as f64).round() as usize;
+            let shown = truncate(&escape(p.key()), key_width, self.settings.truncation()).into_owned();
+
+            write!(
+                writer,
+                "<text x=\"{}\" y=\"{}\" text-anchor=\"end\" fill=\"{}\">",
+                key_right,
+                y + 14,
+                key
+            )?;
+            writeln!(writer, "{}</text>", xml_text(&shown))?;
+            write!(
+                writer,
+                "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\"",
+                bar_left,
+                y + 3,
+                length,
+                CHART_ROW - 6
+            )?;
+            writeln!(writer, " fill=\"{}\"/>", bar)?;
+            write!(writer, "<text x=\"{}\" y=\"{}\">", bar_left + length + CHART_COLUMN, y + 14)?;
+            write!(writer, "<tspan fill=\"{}\">{}</tspan> ", count, count_label)?;
+            writeln!(writer, "<tspan fill=\"{}\">{}</tspan></text>", pct, pct_label)?;
+        }
+        writeln!(writer, "</svg>")?;
+        Ok(())
+    }
+
+    /// Writes a self-contained HTML page holding the SVG chart.
+    pub fn write_html<T: io::Write>(&self, writer: &mut T, pairlist: &mut [Pair]) -> io::Result<()> {
+        let title = xml_text(self.settings.title().unwrap_or("Histogram")).into_owned();
+        writeln!(writer, "<!DOCTYPE html>")?;
+        writeln!(writer, "<html>")?;
+        writeln!(writer, "<head>")?;
+        writeln!(writer, "<meta charset=\"utf-8\">")?;
+        writeln!(writer, "<title>{}</title>", title)?;
+        writeln!(writer, "</head>")?;
+        writeln!(writer, "<body>")?;
+        self.write_svg(writer, pairlist)?;
+        writeln!(writer, "</body>")?;
+        writeln!(writer, "</html>")?;
+        Ok(())
+    }
+
+    /// Writes the rows as an OpenMetrics gauge or counter family with a sample per key, for
+    /// collectors such as the node-exporter textfile collector.
+    pub fn write_openmetrics<T: io::Write>(&self, writer: &mut T, pairlist: &mut [Pair]) -> io::Result<()> {
+        let (family, kind, suffix) = match self.settings.metric_type() {
+            MetricType::Gauge => (self.settings.metric_name(), "gauge", ""),
+            // the samples of counters end in _total, which the family name leaves off
+            MetricType::Counter => {
+                let name = self.settings.metric_name();
+                (name.strip_suffix("_total").unwrap_or(name), "counter", "_total")
+            }
+        };
+        let label = self.settings.metric_label();
+
+        self.sort(pairlist);
+        // a collector should get every key, unless the rows were limited on purpose
+        let limit = if self.settings.height_given() {
+            self.height
+        } else {
+            pairlist.len()
+        };
+        let selection = self.select_within(pairlist, limit);
+
+        writeln!(writer, "# TYPE {} {}", family, kind)?;
+        writeln!(writer, "# HELP {} Number of lines counted for each {}.", family, label)?;
+        for p in &selection.rows {
+            writeln!(
+                writer,
+                "{}{}{{{}=\"{}\"}} {}",
+                family,
+                suffix,
+                label,
+                label_value(p.key()),
+                p.value()
+            )?;
+        }
+        // a family of its own, since any label value given to the rest could also be a real key
+        if self.settings.other() && selection.hidden > 0 {
+            writeln!(writer, "# TYPE {}_other {}", family, kind)?;
+            writeln!(
+                writer,
+                "# HELP {}_other Number of lines counted for the {}s not listed.",
+                family, label
+            )?;
+            writeln!(writer, "{}_other{} {}", family, suffix, selection.other.value())?;
+        }
+        writeln!(writer, "# EOF")?;
+        Ok(())
+    }
+
+    /// Writes the counts as one line of block glyphs scaled from the smallest count to the largest,
+    /// optionally between those counts.
+    pub fn write_sparkline<T: io::Write>(&self, writer: &mut T, pairlist: &mut [Pair]) -> io::Result<()> {
+        self.sort(pairlist);
+        let pairlist: &[Pair] = pairlist;
+        let annotate = self.settings.annotate();
+        // room for the smallest and largest counts either side of the line
+        let limit = if annotate {
+            self.width.saturating_sub(2 * (Self::count_width(pairlist) + 1))
+        } else {
+            self.width
+        };
+        let selection = self.select_within(pairlist, limit);
+        let mut data = selection.rows.clone();
+        if self.settings.other() && selection.hidden > 0 {
+            data.push(&selection.other);
+        }
+
+        let min_value = data.iter().map(|p| p.value()).min().unwrap_or(0);
+        let max_value = data.iter().map(|p| p.value()).max().unwrap_or(0);
+        let top = VERTICAL_BLOCKS.len() - 1;
+        let line: String = data
+            .iter()
+            .map(|p| match max_value - min_value {
+                // all the same
+                0 => VERTICAL_BLOCKS[top / 2],
+                range => VERTICAL_BLOCKS[((p.value() - min_value) as f64 / range as f64 * top as f64).round() as usize],
+            })
+            .collect();
+
+        if annotate && !data.is_empty() {
+            write!(
+                writer,
+                "{}{}{} ",
+                self.settings.ct_colour(),
+                min_value,
+                self.settings.regular_colour()
+            )?;
+            write!(writer, "{}{}{}", self.settings.graph_colour(), line, self.settings.regular_colour())?;
+            writeln!(
+                writer,
+                " {}{}{}",
+                self.settings.ct_colour(),
+                max_value,
+                self.settings.regular_colour()
+            )
+        } else {
+            writeln!(writer, "{}{}{}", self.settings.graph_colour(), line, self.settings.regular_colour())
+        }
+    }
+
+    fn write_rows<T: io::Write>(&self, writer: &mut T, pairlist: &mut [Pair], breakdown: Option<&Breakdown>) -> io::Result<()> {
+        self.sort(pairlist);
+        let pairlist: &[Pair] = pairlist;
+        let selection = self.select(pairlist);
+        let total_value = selection.total;
+        let mut data = selection.rows.clone();
+
+        let other_segments = breakdown.map(|b| {
+            let mut segments = vec![0; b.categories().len()];
+            for p in pairlist {
+                for (sum, n) in segments.iter_mut().zip(b.segments(p.key())) {
+                    *sum += n;
+                }
+            }
+            for p in &data {
+                for (sum, n) in segments.iter_mut().zip(b.segments(p.key())) {
+                    *sum -= n;
+                }
+            }
+            segments
+        });
+        let other = &selection.other;
+        if self.settings.other() && selection.hidden > 0 {
+            data.push(other);
+        }
 
-        let total_value = pairlist.iter().fold(0, |sum, p| sum + p.value());
         let max_value = data.iter().fold(0, |max, p| cmp::max(max, p.value()));
         let max_pct_width = HistogramWriter::pct_width(max_value, total_value);
 
-        let max_key_width = data.iter().fold(0, |max, p| cmp::max(max, p.key().len()));
-        let max_token_width = format!("{}", max_value).len();
+        let longest_key = data.iter().fold(0, |max, p| cmp::max(max, display_width(&self.escape(p.key()))));
+        let max_error = data.iter().fold(0, |max, p| cmp::max(max, p.error()));
+        // approximate counts are prefixed with ~
+        let max_token_width = format!("{}", max_value).len() + usize::from(max_error > 0);
+
+        let mut cumulative = selection.above;
+        let max_cum_width = if self.settings.cumulative() {
+            HistogramWriter::pct_width(data.iter().fold(cumulative, |sum, p| sum + p.value()), total_value) + 1
+        } else {
+            0
+        };
+        let max_key_width = self.key_column_width(longest_key, 1 + max_token_width + 1 + max_pct_width + max_cum_width + 1 + 1);
 
         debug!(
             "[width={}; key={}; token={}; pct={}]",
             self.width, max_key_width, max_token_width, max_pct_width
         );
-        let content_width = max_key_width + 1 + max_token_width + 1 + max_pct_width + 1 + 1;
+        let content_width = max_key_width + 1 + max_token_width + 1 + max_pct_width + max_cum_width + 1 + 1;
         let bar_width = self.width.saturating_sub(content_width);
 
         let mut stderr = io::stderr();
@@ -73,18 +755,27 @@ impl HistogramWriter {
             key: max_key_width,
             token: max_token_width,
             pct: max_pct_width,
+            cum: max_cum_width.saturating_sub(1),
         };
 
+        if max_error > 0 {
+            writeln!(stderr, "counts marked ~ are approximate and overstated by at most {}", max_error)?;
+        }
         self.write_header(&mut stderr, c)?;
 
         for (i, p) in data.iter().enumerate() {
             let pct = p.value() as f64 / total_value as f64 * 100.0f64;
+            cumulative += p.value();
 
-            write!(writer, "{:>width$}", p.key(), width = max_key_width)?;
+            write!(writer, "{}", pad_left(&self.shorten(p.key(), max_key_width), max_key_width))?;
             write!(writer, "{}", self.settings.regular_colour())?;
             write!(writer, "|")?;
             write!(writer, "{}", self.settings.ct_colour())?;
-            write!(writer, "{:>width$}", p.value(), width = max_token_width)?;
+            if p.error() > 0 {
+                write!(writer, "{:>width$}", format!("~{}", p.value()), width = max_token_width)?;
+            } else {
+                write!(writer, "{:>width$}", p.value(), width = max_token_width)?;
+            }
             write!(writer, " ")?;
 
             // A good way to ensure padding is applied is to format your input,
@@ -92,60 +783,382 @@ impl HistogramWriter {
             // https://doc.rust-lang.org/std/fmt/
             write!(writer, "{}", self.settings.pct_colour())?;
             write!(writer, "{:>width$}", format!("({:2.2}%)", pct), width = max_pct_width)?;
+            if max_cum_width > 0 {
+                let cum_pct = cumulative as f64 / total_value as f64 * 100.0f64;
+                write!(writer, " {:>width$}", format!("({:2.2}%)", cum_pct), width = max_cum_width - 1)?;
+            }
 
             write!(writer, "{}", self.settings.graph_colour())?;
-            write!(writer, " {}", self.histogram_bar(max_value, bar_width, p.value()))?;
+            match breakdown {
+                Some(_) if std::ptr::eq(*p, other) => write!(
+                    writer,
+                    " {}",
+                    self.stacked_bar(max_value, bar_width, other_segments.as_deref().unwrap_or(&[]))
+                )?,
+                Some(b) => write!(writer, " {}", self.stacked_bar(max_value, bar_width, b.segments(p.key())))?,
+                None => write!(writer, " {}", self.histogram_bar(max_value, bar_width, p.value()))?,
+            }
 
-            if i == output_limit - 1 {
+            if i == data.len() - 1 {
                 writeln!(writer, "{}", self.settings.regular_colour())?;
             } else {
                 writeln!(writer, "{}", self.settings.key_colour())?;
             }
         }
+
+        if self.settings.totals() {
+            self.write_totals(&mut stderr, total_value, pairlist.len())?;
+        }
+        Ok(())
+    }
+
+    // a column per key, one character wide with a space after, growing up --height rows from an axis
+    // along the bottom, with the keys written downwards beneath it
+    fn write_columns<T: io::Write>(&self, writer: &mut T, pairlist: &mut [Pair]) -> io::Result<()> {
+        self.sort(pairlist);
+        let pairlist: &[Pair] = pairlist;
+        let axis_width = Self::count_width(pairlist);
+        let selection = self.select_within(pairlist, self.width.saturating_sub(axis_width + 1) / 2);
+        let mut data = selection.rows.clone();
+        if self.settings.other() && selection.hidden > 0 {
+            data.push(&selection.other);
+        }
+        let max_value = data.iter().fold(0, |max, p| cmp::max(max, p.value()));
+
+        // partial blocks split each row into eighths
+        let graph_chars = self.settings.graph_chars();
+        let steps = if self.settings.char_width() < 1.0 { graph_chars.len() } else { 1 };
+        let graphemes: Vec<&str> = self.settings.histogram_char().graphemes(true).collect();
+        let body = if steps > 1 {
+            graph_chars.last().expect("graph_chars is empty").to_string()
+        } else {
+            graphemes.first().expect("histogram_char is empty").to_string()
+        };
+        // with two graphemes, the second tops each column
+        let tip = if steps == 1 && graphemes.len() > 1 {
+            graphemes[1]
+        } else {
+            body.as_str()
+        };
+
+        let heights: Vec<usize> = data
+            .iter()
+            .map(|p| match p.value() {
+                0 => 0,
+                v => cmp::max((v as f64 / max_value as f64 * (self.height * steps) as f64).round() as usize, 1),
+            })
+            .collect();
+
+        for row in (0..self.height).rev() {
+            let label = if row + 1 == self.height {
+                max_value.to_string()
+            } else {
+                String::new()
+            };
+            let mut cells = String::new();
+            for &height in &heights {
+                let (full, rem) = (height / steps, height % steps);
+                if row + 1 == full {
+                    cells.push_str(tip);
+                } else if row < full {
+                    cells.push_str(&body);
+                } else if row == full && rem > 0 {
+                    cells.push(graph_chars[rem - 1]);
+                } else {
+                    cells.push(' ');
+                }
+                cells.push(' ');
+            }
+            write!(writer, "{}{:>width$}", self.settings.ct_colour(), label, width = axis_width)?;
+            write!(writer, "{}|", self.settings.regular_colour())?;
+            write!(writer, "{}{}", self.settings.graph_colour(), cells.trim_end())?;
+            writeln!(writer, "{}", self.settings.regular_colour())?;
+        }
+        writeln!(writer, "{:>width$}+{}", "", "-".repeat(data.len() * 2), width = axis_width)?;
+
+        // keys too long to write down in full are shortened, as with --key-width
+        let longest_key = data.iter().fold(0, |max, p| cmp::max(max, display_width(&self.escape(p.key()))));
+        let label_rows = cmp::min(
+            longest_key,
+            if self.settings.key_width() > 0 {
+                self.settings.key_width()
+            } else {
+                MAX_LABEL_ROWS
+            },
+        );
+        let labels: Vec<Vec<String>> = data
+            .iter()
+            .map(|p| self.shorten(p.key(), label_rows).graphemes(true).map(String::from).collect())
+            .collect();
+        for row in 0..labels.iter().fold(0, |max, l| cmp::max(max, l.len())) {
+            let mut line = String::new();
+            for label in &labels {
+                let g = label.get(row).map_or(" ", String::as_str);
+                // wide characters fill the space between columns too
+                line.push_str(g);
+                line.push_str(&" ".repeat(2usize.saturating_sub(grapheme_width(g))));
+            }
+            write!(writer, "{:>width$} {}", "", self.settings.key_colour(), width = axis_width)?;
+            writeln!(writer, "{}{}", line.trim_end(), self.settings.regular_colour())?;
+        }
+
+        if self.settings.totals() {
+            self.write_totals(&mut io::stderr(), selection.total, pairlist.len())?;
+        }
         Ok(())
     }
 
+    // narrower than the longest key when needed to leave at least --min-bar columns for the bars
+    fn key_column_width(&self, longest_key: usize, other_columns: usize) -> usize {
+        let mut width = longest_key;
+        if self.settings.key_width() > 0 {
+            width = cmp::min(width, self.settings.key_width());
+        }
+        let room = self.width.saturating_sub(other_columns + self.settings.min_bar());
+        cmp::min(width, cmp::max(room, MIN_KEY_WIDTH))
+    }
+
+    // keys as they are printed: escaped, then shortened to fit the key column
+    fn shorten(&self, key: &str, width: usize) -> String {
+        truncate(&self.escape(key), width, self.settings.truncation()).into_owned()
+    }
+
+    fn escape<'a>(&self, key: &'a str) -> Cow<'a, str> {
+        if self.settings.raw() {
+            Cow::Borrowed(key)
+        } else {
+            escape(key)
+        }
+    }
+
+    fn sort(&self, pairlist: &mut [Pair]) {
+        match self.settings.sort_order() {
+            SortOrder::Count => pairlist.sort_by(|a, b| b.cmp(a)),
+            SortOrder::CountAscending => pairlist.sort_by(|a, b| a.value().cmp(&b.value()).then(a.key().cmp(b.key()))),
+            SortOrder::Key => pairlist.sort_by(|a, b| a.key().cmp(b.key())),
+            SortOrder::Natural => pairlist.sort_by(|a, b| natural_cmp(a.key(), b.key())),
+            SortOrder::Input | SortOrder::Change => {}
+        }
+    }
+
+    // the window of rows within the count thresholds to show, from a sorted pairlist
+    fn select<'a>(&self, pairlist: &'a [Pair]) -> Selection<'a> {
+        self.select_within(pairlist, self.height)
+    }
+
+    // as select, showing at most `limit` rows rather than --height
+    fn select_within<'a>(&self, pairlist: &'a [Pair], limit: usize) -> Selection<'a> {
+        let total = pairlist.iter().fold(0, |sum, p| sum + p.value());
+        let selected: Vec<&Pair> = pairlist.iter().filter(|p| self.in_range(p.value())).collect();
+        let counts: Vec<u64> = selected.iter().map(|p| p.value()).collect();
+        let (start, end) = self.window(&counts, total, limit);
+        let rows = selected[start..end].to_vec();
+
+        let shown_value = rows.iter().fold(0, |sum, p| sum + p.value());
+        let shown_error = rows.iter().fold(0, |sum, p| sum + p.error());
+        let total_error = pairlist.iter().fold(0, |sum, p| sum + p.error());
+        let hidden = pairlist.len() - rows.len();
+        let hidden_keys = self.input_keys(pairlist.len()).saturating_sub(rows.len() as u64);
+        Selection {
+            first_rank: start + 1,
+            above: selected[..start].iter().fold(0, |sum, p| sum + p.value()),
+            total,
+            other: Pair::approximate(
+                total - shown_value,
+                total_error - shown_error,
+                &format!("(other {} keys)", self.keys_label(hidden_keys)),
+            ),
+            hidden,
+            rows,
+        }
+    }
+
+    fn in_range(&self, value: u64) -> bool {
+        value >= self.settings.min_count() && (self.settings.max_count() == 0 || value <= self.settings.max_count())
+    }
+
+    // the rows to show, given the counts of the selected rows: from --offset down to `limit` rows (or
+    // to --cutoff), or with --bottom, the last rows before --offset from the end
+    fn window(&self, counts: &[u64], total_value: u64, limit: usize) -> (usize, usize) {
+        let offset = cmp::min(self.settings.offset(), counts.len());
+        if self.settings.bottom() > 0 {
+            let end = counts.len() - offset;
+            (end.saturating_sub(self.settings.bottom()), end)
+        } else if self.settings.cutoff() > 0.0 {
+            let end = HistogramWriter::cutoff_limit(counts, total_value, self.settings.cutoff());
+            (offset, cmp::max(offset, end))
+        } else {
+            (offset, cmp::min(offset + limit, counts.len()))
+        }
+    }
+
+    // enough rows, largest first, to reach `cutoff` percent of the total
+    fn cutoff_limit(counts: &[u64], total_value: u64, cutoff: f64) -> usize {
+        let mut cumulative = 0;
+        for (i, count) in counts.iter().enumerate() {
+            cumulative += count;
+            if cumulative as f64 / total_value as f64 * 100.0 >= cutoff {
+                return i + 1;
+            }
+        }
+        counts.len()
+    }
+
+    fn write_totals<W: io::Write>(&self, w: &mut W, total: u64, kept: usize) -> io::Result<()> {
+        writeln!(
+            w,
+            "total: {}  keys: {}",
+            HistogramWriter::with_commas(total),
+            self.keys_label(self.input_keys(kept))
+        )
+    }
+
+    fn stack_colour(&self, i: usize) -> String {
+        if self.settings.colourised_output() {
+            format!("\u{001b}[{}m", STACK_COLOURS[i])
+        } else {
+            String::new()
+        }
+    }
+
+    // segment ends are rounded from the running total so the segments add up to the whole bar
+    fn stacked_bar(&self, max_value: u64, bar_width: usize, segments: &[u64]) -> String {
+        let mut bar = String::new();
+        let mut total = 0;
+        let mut end = 0;
+        for (i, &segment) in segments.iter().enumerate() {
+            total += segment;
+            let start = end;
+            end = ((total as f64) / (max_value as f64) * (bar_width as f64)).round() as usize;
+            if end > start {
+                bar.push_str(&self.stack_colour(i));
+                bar.push_str(&STACK_CHARS[i].to_string().repeat(end - start));
+            }
+        }
+        bar
+    }
+
+    // full blocks, then a partial block for the remaining eighths of a column
+    fn block_bar(max_value: u64, bar_width: usize, bar_value: u64) -> String {
+        if bar_value == 0 {
+            return String::new();
+        }
+        // any count at all gets at least a sliver
+        let eighths = cmp::max((bar_value as f64 / max_value as f64 * (bar_width * 8) as f64).round() as usize, 1);
+        let mut bar = HORIZONTAL_BLOCKS[7].to_string().repeat(eighths / 8);
+        let rem = eighths % 8;
+        if rem > 0 {
+            bar.push(HORIZONTAL_BLOCKS[rem - 1]);
+        }
+        bar
+    }
+
     fn histogram_bar(&self, max_value: u64, bar_width: usize, bar_value: u64) -> String {
-        let zero_char: char;
-        let one_char: char;
+        if bar_value == 0 {
+            return String::new();
+        }
+
+        // one grapheme for the body of the bar, and optionally a second for its tip
+        let zero_char: String;
+        let one_char: String;
         let histogram_char = self.settings.histogram_char();
         let char_width = self.settings.char_width();
+        let graphemes: Vec<&str> = histogram_char.graphemes(true).collect();
         if char_width < 1.0 {
-            zero_char = *self.settings.graph_chars().last().expect("graph_chars is empty");
-            one_char = '\0'
-        } else if histogram_char.len() > 1 && !self.settings.unicode_mode() {
-            zero_char = histogram_char.chars().next().unwrap();
-            one_char = histogram_char.chars().nth(1).unwrap();
+            zero_char = self.settings.graph_chars().last().expect("graph_chars is empty").to_string();
+            one_char = String::new();
+        } else if graphemes.len() > 1 {
+            zero_char = graphemes[0].to_string();
+            one_char = graphemes[1].to_string();
         } else {
-            zero_char = histogram_char.chars().next().expect("histogram_char is empty");
-            one_char = zero_char;
+            zero_char = graphemes.first().expect("histogram_char is empty").to_string();
+            one_char = zero_char.clone();
         }
 
         let width = (bar_value as f64) / (max_value as f64) * (bar_width as f64);
         let int_width = width.floor() as usize;
         let rem = width - int_width as f64;
-        let graph_char = vec!['▏', '▎', '▍', '▌', '▋', '▊', '▉', '█'];
 
+        // wide characters fill two columns each
         #[allow(clippy::disallowed_names)]
-        let mut bar = zero_char.to_string().repeat(int_width);
+        let mut bar = zero_char.repeat(int_width / cmp::max(display_width(&zero_char), 1));
 
-        if (char_width - 1.0).abs() < std::f64::EPSILON {
-            bar.push(one_char);
+        if (char_width - 1.0).abs() < f64::EPSILON {
+            bar.push_str(&one_char);
         } else if char_width < 1.0 && rem > char_width {
             let which = (rem / char_width).floor() as usize;
-            bar.push(graph_char[which])
+            bar.push(self.settings.graph_chars()[which])
         }
 
         bar
     }
 }
+
+// the colour SGR parameters such as "34", "1;92", "38;5;208" or "38;2;255;128;0" draw in a terminal,
+// or None for the default
+fn css_colour(sgr: &str) -> Option<String> {
+    const NORMAL: [&str; 8] = [
+        "#000000", "#c00000", "#00a000", "#c0a000", "#0000c0", "#a000a0", "#00a0a0", "#c0c0c0",
+    ];
+    const BRIGHT: [&str; 8] = [
+        "#606060", "#ff4040", "#40e040", "#ffff40", "#4040ff", "#ff40ff", "#40ffff", "#ffffff",
+    ];
+    let params: Vec<usize> = sgr.split(';').filter_map(|p| p.trim().parse().ok()).collect();
+    let mut colour = None;
+    let mut i = 0;
+    while i < params.len() {
+        match params[i..] {
+            [38, 5, n, ..] => {
+                colour = Some(xterm_colour(n, &NORMAL, &BRIGHT));
+                i += 3;
+            }
+            [38, 2, r, g, b, ..] => {
+                colour = Some(format!("#{:02x}{:02x}{:02x}", r, g, b));
+                i += 5;
+            }
+            [n @ 30..=37, ..] => {
+                colour = Some(NORMAL[n - 30].to_string());
+                i += 1;
+            }
+            [n @ 90..=97, ..] => {
+                colour = Some(BRIGHT[n - 90].to_string());
+                i += 1;
+            }
+            _ => i += 1,
+        }
+    }
+    colour
+}
+
+// the 256-colour palette: the 16 basic colours, a 6x6x6 cube, then 24 greys
+fn xterm_colour(n: usize, normal: &[&str; 8], bright: &[&str; 8]) -> String {
+    const LEVELS: [usize; 6] = [0, 95, 135, 175, 215, 255];
+    match n {
+        0..=7 => normal[n].to_string(),
+        8..=15 => bright[n - 8].to_string(),
+        16..=231 => {
+            let n = n - 16;
+            format!("#{:02x}{:02x}{:02x}", LEVELS[n / 36], LEVELS[n / 6 % 6], LEVELS[n % 6])
+        }
+        _ => {
+            let grey = 8 + 10 * (n.min(255) - 232);
+            format!("#{:02x}{:02x}{:02x}", grey, grey, grey)
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::compare::Delta;
     use crate::pairlist::Pair;
     use crate::settings::Settings;
+    use crate::stacked::StackTokenizer;
+    use crate::statistics::Statistics;
+    use crate::summary::Summary;
 
     use std::io::Cursor;
+    use std::time::Duration;
 
     macro_rules! args {
         ( $( $x:expr ),* ) => {
@@ -175,7 +1188,12 @@ mod test {
 
         let s = Settings::new(args!["--width=10"]);
         let h = HistogramWriter::new(s);
-        let c = ColumnWidths { key: 3, token: 3, pct: 3 };
+        let c = ColumnWidths {
+            key: 3,
+            token: 3,
+            pct: 3,
+            cum: 0,
+        };
 
         h.write_header(&mut buff, c).unwrap();
 
@@ -185,12 +1203,60 @@ mod test {
     }
 
     #[test]
-    fn histogram_bar_one_char() {
+    fn write_summary() {
+        let mut buff = Cursor::new(Vec::new());
+
         let s = Settings::new(args![]);
         let h = HistogramWriter::new(s);
-        let bar = h.histogram_bar(16, 32, 8);
-        assert_eq!(bar, "-----------------");
-    }
+        let summary = Summary::new(1234567, 1000, 12).with_runtime(Duration::from_micros(3_243_409));
+
+        h.write_summary(&mut buff, &summary).unwrap();
+
+        let expected = "tokens/lines examined: 1,234,567\n tokens/lines matched: 1,000\n       histogram keys: 12\n              runtime: 3,243.41ms\n";
+        assert_eq!(String::from_utf8_lossy(buff.get_ref()), expected);
+    }
+
+    #[test]
+    fn write_summary_estimated() {
+        let mut buff = Cursor::new(Vec::new());
+
+        let s = Settings::new(args![]);
+        let h = HistogramWriter::new(s);
+        let summary = Summary::new(10, 10, 2)
+            .with_distinct_estimate(7)
+            .with_runtime(Duration::from_micros(999_999));
+
+        h.write_summary(&mut buff, &summary).unwrap();
+
+        let summary = String::from_utf8_lossy(buff.get_ref());
+        assert!(summary.contains(" distinct keys (est.): ~7\n"));
+        assert!(summary.ends_with("runtime: 1,000.00ms\n"));
+    }
+
+    #[test]
+    fn write_statistics() {
+        let mut buff = Cursor::new(Vec::new());
+
+        let s = Settings::new(args!["--stats"]);
+        let h = HistogramWriter::new(s);
+        let mut stats = Statistics::new();
+        for v in [2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0] {
+            stats.insert(v);
+        }
+
+        h.write_statistics(&mut buff, &stats).unwrap();
+
+        let expected = "count: 8  min: 2  max: 9  mean: 5  stddev: 2\nmedian: 4.5  p90: 7.6  p95: 8.3  p99: 8.86  p99.9: 8.986\n";
+        assert_eq!(String::from_utf8_lossy(buff.get_ref()), expected);
+    }
+
+    #[test]
+    fn histogram_bar_one_char() {
+        let s = Settings::new(args![]);
+        let h = HistogramWriter::new(s);
+        let bar = h.histogram_bar(16, 32, 8);
+        assert_eq!(bar, "-----------------");
+    }
 
     #[test]
     fn histogram_bar_two_char() {
@@ -235,4 +1301,787 @@ mod test {
 
         assert_eq!(hist, "b|2 (66.67%) --\na|1 (33.33%) -\n");
     }
+
+    #[test]
+    fn histogram_bar_zero() {
+        let s = Settings::new(args![]);
+        let h = HistogramWriter::new(s);
+        let bar = h.histogram_bar(16, 32, 0);
+        assert_eq!(bar, "");
+    }
+
+    #[test]
+    fn write_histogram_bins_in_order() {
+        let s = Settings::new(args!["--bins=3", "--width=22", "--min-bar=0"]);
+        let h = HistogramWriter::new(s);
+
+        let mut counts: Vec<Pair> = vec![Pair::new(1, "[0, 3)"), Pair::new(0, "[3, 6)"), Pair::new(3, "[6, 9]")];
+        let mut buf = io::Cursor::new(Vec::new());
+        h.write_histogram(&mut buf, &mut counts).unwrap();
+
+        let hist = String::from_utf8_lossy(buf.get_ref());
+
+        assert_eq!(hist, "[0, 3)|1 (25.00%) --\n[3, 6)|0  (0.00%) \n[6, 9]|3 (75.00%) ----\n");
+    }
+
+    #[test]
+    fn write_histogram_approximate() {
+        let s = Settings::new(args!["--width=18"]);
+        let h = HistogramWriter::new(s);
+
+        let mut counts: Vec<Pair> = vec![Pair::approximate(3, 1, "a"), Pair::new(1, "b")];
+        let mut buf = io::Cursor::new(Vec::new());
+        h.write_histogram(&mut buf, &mut counts).unwrap();
+
+        let hist = String::from_utf8_lossy(buf.get_ref());
+
+        assert_eq!(hist, "a|~3 (75.00%) ----\nb| 1 (25.00%) --\n");
+    }
+
+    #[test]
+    fn write_stacked_histogram() {
+        let s = Settings::new(args!["--stack", "--width=23"]);
+        let h = HistogramWriter::new(s);
+
+        let input = "GET 200\nGET 404\nPOST 200\nGET 200\n";
+        let (mut counts, breakdown, _) = StackTokenizer::new("").count(input.as_bytes());
+        let mut buf = io::Cursor::new(Vec::new());
+        h.write_stacked_histogram(&mut buf, &mut counts, &breakdown).unwrap();
+
+        let hist = String::from_utf8_lossy(buf.get_ref());
+
+        assert_eq!(hist, " GET|3 (75.00%) ####==\nPOST|1 (25.00%) ##\n");
+    }
+
+    #[test]
+    fn write_legend() {
+        let s = Settings::new(args!["--stack"]);
+        let h = HistogramWriter::new(s);
+
+        let (_, breakdown, _) = StackTokenizer::new("").count("GET 200\nGET 404\nPOST 200\n".as_bytes());
+        let mut buf = io::Cursor::new(Vec::new());
+        h.write_legend(&mut buf, &breakdown).unwrap();
+
+        assert_eq!(String::from_utf8_lossy(buf.get_ref()), "Legend: # 200 = 404\n");
+    }
+
+    #[test]
+    fn write_legend_colour() {
+        let s = Settings::new(args!["--stack", "--color"]);
+        let h = HistogramWriter::new(s);
+
+        let (_, breakdown, _) = StackTokenizer::new("").count("GET 200\nGET 404\n".as_bytes());
+        let mut buf = io::Cursor::new(Vec::new());
+        h.write_legend(&mut buf, &breakdown).unwrap();
+
+        assert_eq!(
+            String::from_utf8_lossy(buf.get_ref()),
+            "Legend: \u{1b}[32m#\u{1b}[0m 200 \u{1b}[33m=\u{1b}[0m 404\n"
+        );
+    }
+
+    #[test]
+    fn write_comparison() {
+        let s = Settings::new(args!["--compare=baseline", "--width=34"]);
+        let h = HistogramWriter::new(s);
+
+        let mut deltas = vec![Delta::new("a", 4, 2), Delta::new("b", 0, 1), Delta::new("c", 2, 8)];
+        let mut buf = io::Cursor::new(Vec::new());
+        h.write_comparison(&mut buf, &mut deltas).unwrap();
+
+        let expected = concat!(
+            "  c| 2                  ===\n",
+            "   | 8    +6 (+300.00%) ----------\n",
+            "  a| 4                  =====\n",
+            "   | 2    -2  (-50.00%) ---\n",
+            "  b| 0                  \n",
+            "   | 1    +1      (new) --\n",
+        );
+        assert_eq!(String::from_utf8_lossy(buf.get_ref()), expected);
+    }
+
+    #[test]
+    fn write_comparison_by_change() {
+        let s = Settings::new(args!["--compare=baseline", "--by-change", "--width=28"]);
+        let h = HistogramWriter::new(s);
+
+        let mut deltas = vec![Delta::new("a", 4, 2), Delta::new("b", 0, 1), Delta::new("c", 2, 8)];
+        let mut buf = io::Cursor::new(Vec::new());
+        h.write_comparison(&mut buf, &mut deltas).unwrap();
+
+        let keys: Vec<&str> = deltas.iter().map(Delta::key).collect();
+        assert_eq!(keys, ["c", "a", "b"]);
+    }
+
+    #[test]
+    fn write_comparison_sort_orders() {
+        let keys = |order: &str| {
+            let s = Settings::new(args!["--compare=baseline", format!("--sort={}", order)]);
+            let h = HistogramWriter::new(s);
+            let mut deltas = vec![Delta::new("a", 4, 2), Delta::new("b", 0, 1), Delta::new("c", 2, 8)];
+            let mut buf = io::Cursor::new(Vec::new());
+            h.write_comparison(&mut buf, &mut deltas).unwrap();
+            deltas.iter().map(|d| d.key().to_string()).collect::<Vec<_>>()
+        };
+        assert_eq!(keys("input"), ["a", "b", "c"]);
+        assert_eq!(keys("count-asc"), ["b", "a", "c"]);
+    }
+
+    #[test]
+    fn write_comparison_selection() {
+        let s = Settings::new(args!["--compare=baseline", "--offset=1", "--min-count=1", "--other", "--width=50"]);
+        let h = HistogramWriter::new(s);
+
+        let mut deltas = vec![
+            Delta::new("a", 4, 2),
+            Delta::new("b", 0, 1),
+            Delta::new("c", 2, 8),
+            Delta::new("d", 1, 0),
+        ];
+        let mut buf = io::Cursor::new(Vec::new());
+        h.write_comparison(&mut buf, &mut deltas).unwrap();
+
+        let expected = concat!(
+            "             a| 4                  ========\n",
+            "              | 2    -2  (-50.00%) ----\n",
+            "             b| 0                  \n",
+            "              | 1    +1      (new) --\n",
+            "(other 2 keys)| 3                  ======\n",
+            "              | 8    +5 (+166.67%) ---------------\n",
+        );
+        assert_eq!(String::from_utf8_lossy(buf.get_ref()), expected);
+    }
+
+    #[test]
+    fn write_histogram_other() {
+        let s = Settings::new(args!["--other", "--height=2", "--width=32", "--min-bar=0"]);
+        let h = HistogramWriter::new(s);
+
+        let mut counts: Vec<Pair> = vec![Pair::new(4, "a"), Pair::new(1, "b"), Pair::new(2, "c"), Pair::new(1, "d")];
+        let mut buf = io::Cursor::new(Vec::new());
+        h.write_histogram(&mut buf, &mut counts).unwrap();
+
+        let expected = concat!(
+            "             a|4 (50.00%) ------\n",
+            "             c|2 (25.00%) ---\n",
+            "(other 2 keys)|2 (25.00%) ---\n",
+        );
+        assert_eq!(String::from_utf8_lossy(buf.get_ref()), expected);
+    }
+
+    #[test]
+    fn write_histogram_other_everything_shown() {
+        let s = Settings::new(args!["--other", "--width=15"]);
+        let h = HistogramWriter::new(s);
+
+        let mut counts: Vec<Pair> = vec![Pair::new(1, "a"), Pair::new(2, "b")];
+        let mut buf = io::Cursor::new(Vec::new());
+        h.write_histogram(&mut buf, &mut counts).unwrap();
+
+        assert_eq!(String::from_utf8_lossy(buf.get_ref()), "b|2 (66.67%) --\na|1 (33.33%) -\n");
+    }
+
+    #[test]
+    fn write_totals() {
+        let s = Settings::new(args!["--totals"]);
+        let h = HistogramWriter::new(s);
+
+        let mut buf = io::Cursor::new(Vec::new());
+        h.write_totals(&mut buf, 12345, 1000).unwrap();
+
+        assert_eq!(String::from_utf8_lossy(buf.get_ref()), "total: 12,345  keys: 1,000\n");
+    }
+
+    #[test]
+    fn write_totals_estimated() {
+        let s = Settings::new(args!["--totals", "--other", "--height=1", "--approx=2"]);
+        let h = HistogramWriter::new(s).with_distinct_estimate(300);
+
+        let mut buf = io::Cursor::new(Vec::new());
+        h.write_totals(&mut buf, 12345, 2).unwrap();
+        assert_eq!(String::from_utf8_lossy(buf.get_ref()), "total: 12,345  keys: ~300\n");
+
+        let counts: Vec<Pair> = vec![Pair::new(5, "a"), Pair::approximate(3, 2, "b")];
+        assert_eq!(h.select(&counts).other.key(), "(other ~299 keys)");
+    }
+
+    #[test]
+    fn write_header_cumulative() {
+        let mut buff = Cursor::new(Vec::new());
+
+        let s = Settings::new(args!["--width=10", "--cumulative"]);
+        let h = HistogramWriter::new(s);
+        let c = ColumnWidths {
+            key: 3,
+            token: 3,
+            pct: 3,
+            cum: 5,
+        };
+
+        h.write_header(&mut buff, c).unwrap();
+
+        let header = String::from_utf8_lossy(buff.get_ref());
+
+        assert_eq!(header, "Key| Ct (Pct) (Cum) Histogram\n---|------\n");
+    }
+
+    #[test]
+    fn write_histogram_cumulative() {
+        let s = Settings::new(args!["--cumulative", "--width=30"]);
+        let h = HistogramWriter::new(s);
+
+        let mut counts: Vec<Pair> = vec![Pair::new(1, "a"), Pair::new(2, "b"), Pair::new(1, "c")];
+        let mut buf = io::Cursor::new(Vec::new());
+        h.write_histogram(&mut buf, &mut counts).unwrap();
+
+        let expected = concat!(
+            "b|2 (50.00%)  (50.00%) -------\n",
+            "c|1 (25.00%)  (75.00%) ----\n",
+            "a|1 (25.00%) (100.00%) ----\n",
+        );
+        assert_eq!(String::from_utf8_lossy(buf.get_ref()), expected);
+    }
+
+    #[test]
+    fn cutoff_limit() {
+        let counts = [5, 3, 1, 1];
+        assert_eq!(HistogramWriter::cutoff_limit(&counts, 10, 50.0), 1);
+        assert_eq!(HistogramWriter::cutoff_limit(&counts, 10, 80.0), 2);
+        assert_eq!(HistogramWriter::cutoff_limit(&counts, 10, 81.0), 3);
+        assert_eq!(HistogramWriter::cutoff_limit(&counts, 10, 100.0), 4);
+    }
+
+    #[test]
+    fn write_histogram_sort_orders() {
+        let keys = |order: &str| {
+            let s = Settings::new(args![format!("--sort={}", order)]);
+            let h = HistogramWriter::new(s);
+            let mut counts: Vec<Pair> = vec![Pair::new(2, "10"), Pair::new(3, "9"), Pair::new(1, "b"), Pair::new(2, "a")];
+            let mut buf = io::Cursor::new(Vec::new());
+            h.write_histogram(&mut buf, &mut counts).unwrap();
+            counts.iter().map(|p| p.key().to_string()).collect::<Vec<_>>()
+        };
+
+        assert_eq!(keys("count"), ["9", "a", "10", "b"]);
+        assert_eq!(keys("count-asc"), ["b", "10", "a", "9"]);
+        assert_eq!(keys("key"), ["10", "9", "a", "b"]);
+        assert_eq!(keys("natural"), ["9", "10", "a", "b"]);
+        assert_eq!(keys("input"), ["10", "9", "b", "a"]);
+    }
+
+    fn shown_keys(args: Vec<&str>) -> Vec<String> {
+        let s = Settings::new(args!["--width=30", "--min-bar=0"].chain(args.into_iter().map(String::from)));
+        let h = HistogramWriter::new(s);
+        let mut counts: Vec<Pair> = (1..=6).map(|i| Pair::new(i, &format!("k{}", i))).collect();
+        let mut buf = io::Cursor::new(Vec::new());
+        h.write_histogram(&mut buf, &mut counts).unwrap();
+
+        let hist = String::from_utf8_lossy(buf.get_ref()).to_string();
+        hist.lines().map(|l| l.split('|').next().unwrap().trim().to_string()).collect()
+    }
+
+    #[test]
+    fn write_histogram_bottom() {
+        assert_eq!(shown_keys(vec!["--bottom=2"]), ["k2", "k1"]);
+        assert_eq!(shown_keys(vec!["--bottom=2", "--offset=1"]), ["k3", "k2"]);
+        assert_eq!(shown_keys(vec!["--bottom=2", "--sort=count-asc"]), ["k5", "k6"]);
+    }
+
+    #[test]
+    fn write_histogram_offset() {
+        assert_eq!(shown_keys(vec!["--offset=2", "--limit=2"]), ["k4", "k3"]);
+        assert_eq!(shown_keys(vec!["--offset=5"]), ["k1"]);
+        assert!(shown_keys(vec!["--offset=10"]).is_empty());
+    }
+
+    #[test]
+    fn write_histogram_count_range() {
+        assert_eq!(shown_keys(vec!["--min-count=5"]), ["k6", "k5"]);
+        assert_eq!(shown_keys(vec!["--max-count=2"]), ["k2", "k1"]);
+        assert_eq!(shown_keys(vec!["--min-count=2", "--max-count=4", "--offset=1"]), ["k3", "k2"]);
+        assert_eq!(shown_keys(vec!["--max-count=2", "--other"]), ["k2", "k1", "(other 4 keys)"]);
+    }
+
+    #[test]
+    fn write_histogram_offset_cumulative() {
+        let s = Settings::new(args!["--cumulative", "--offset=1", "--limit=1", "--width=30"]);
+        let h = HistogramWriter::new(s);
+
+        let mut counts: Vec<Pair> = vec![Pair::new(1, "a"), Pair::new(2, "b"), Pair::new(1, "c")];
+        let mut buf = io::Cursor::new(Vec::new());
+        h.write_histogram(&mut buf, &mut counts).unwrap();
+
+        assert_eq!(String::from_utf8_lossy(buf.get_ref()), "c|1 (25.00%) (75.00%) --------\n");
+    }
+
+    #[test]
+    fn write_histogram_long_key() {
+        let s = Settings::new(args!["--width=30"]);
+        let h = HistogramWriter::new(s);
+
+        let mut counts: Vec<Pair> = vec![Pair::new(2, "a very long key that would hide the bars"), Pair::new(1, "b")];
+        let mut buf = io::Cursor::new(Vec::new());
+        h.write_histogram(&mut buf, &mut counts).unwrap();
+
+        assert_eq!(
+            String::from_utf8_lossy(buf.get_ref()),
+            "a very…|2 (66.67%) -----------\n      b|1 (33.33%) ------\n"
+        );
+    }
+
+    #[test]
+    fn write_histogram_key_width() {
+        let s = Settings::new(args!["--width=40", "--key-width=8", "--truncate=path"]);
+        let h = HistogramWriter::new(s);
+
+        let mut counts: Vec<Pair> = vec![Pair::new(1, "/var/log/syslog")];
+        let mut buf = io::Cursor::new(Vec::new());
+        h.write_histogram(&mut buf, &mut counts).unwrap();
+
+        let hist = String::from_utf8_lossy(buf.get_ref());
+        assert!(hist.starts_with("…/syslog|1 (100.00%) "), "{}", hist);
+    }
+
+    #[test]
+    fn histogram_bar_wide_char() {
+        let s = Settings::new(args!["--char=＝"]);
+        let h = HistogramWriter::new(s);
+        let bar = h.histogram_bar(16, 32, 8);
+        assert_eq!(bar, "＝＝＝＝＝＝＝＝＝");
+    }
+
+    #[test]
+    fn histogram_bar_two_graphemes() {
+        let s = Settings::new(args!["--char=e\u{301}>"]);
+        let h = HistogramWriter::new(s);
+        let bar = h.histogram_bar(4, 4, 2);
+        assert_eq!(bar, "e\u{301}e\u{301}>");
+    }
+
+    #[test]
+    fn write_histogram_multibyte_keys() {
+        let s = Settings::new(args!["--width=30"]);
+        let h = HistogramWriter::new(s);
+
+        let mut counts: Vec<Pair> = vec![Pair::new(3, "日本"), Pair::new(2, "café"), Pair::new(1, "🎉")];
+        let mut buf = io::Cursor::new(Vec::new());
+        h.write_histogram(&mut buf, &mut counts).unwrap();
+
+        let expected = concat!(
+            "日本|3 (50.00%) --------------\n",
+            "café|2 (33.33%) ---------\n",
+            "  🎉|1 (16.67%) -----\n",
+        );
+        assert_eq!(String::from_utf8_lossy(buf.get_ref()), expected);
+    }
+
+    #[test]
+    fn write_histogram_truncates_wide_keys() {
+        let s = Settings::new(args!["--width=30", "--key-width=5"]);
+        let h = HistogramWriter::new(s);
+
+        let mut counts: Vec<Pair> = vec![Pair::new(1, "日本語のキー")];
+        let mut buf = io::Cursor::new(Vec::new());
+        h.write_histogram(&mut buf, &mut counts).unwrap();
+
+        let hist = String::from_utf8_lossy(buf.get_ref());
+        assert!(hist.starts_with("日本…|1 "), "{}", hist);
+    }
+
+    #[test]
+    fn write_histogram_escapes_keys() {
+        let s = Settings::new(args!["--width=40"]);
+        let h = HistogramWriter::new(s);
+
+        let mut counts: Vec<Pair> = vec![Pair::new(2, "\u{1b}[2Jx"), Pair::new(1, "a\tb")];
+        let mut buf = io::Cursor::new(Vec::new());
+        h.write_histogram(&mut buf, &mut counts).unwrap();
+
+        let hist = String::from_utf8_lossy(buf.get_ref());
+        assert!(hist.starts_with("\\x1b[2Jx|2 "), "{}", hist);
+        assert!(hist.contains("\n    a\\tb|1 "), "{}", hist);
+    }
+
+    #[test]
+    fn write_histogram_raw_keys() {
+        let s = Settings::new(args!["--width=40", "--raw"]);
+        let h = HistogramWriter::new(s);
+
+        let mut counts: Vec<Pair> = vec![Pair::new(1, "a\tb")];
+        let mut buf = io::Cursor::new(Vec::new());
+        h.write_histogram(&mut buf, &mut counts).unwrap();
+
+        assert!(String::from_utf8_lossy(buf.get_ref()).starts_with("a\tb|1 "));
+    }
+
+    #[test]
+    fn write_json() {
+        let s = Settings::new(args!["--output=json", "--height=2", "--other", "--cumulative"]);
+        let h = HistogramWriter::new(s);
+
+        let mut counts: Vec<Pair> = vec![Pair::new(1, "a\"b"), Pair::new(4, "c"), Pair::approximate(3, 1, "d")];
+        let summary = Summary::new(10, 8, 3).with_runtime(Duration::from_micros(1500));
+        let mut buf = io::Cursor::new(Vec::new());
+        h.write_json(&mut buf, &mut counts, None, &summary, None).unwrap();
+
+        let expected = concat!(
+            "{\n",
+            "  \"rows\": [\n",
+            "    {\"rank\": 1, \"key\": \"c\", \"count\": 4, \"percent\": 50, \"cumulative_percent\": 50},\n",
+            "    {\"rank\": 2, \"key\": \"d\", \"count\": 3, \"percent\": 37.5, \"cumulative_percent\": 87.5, \"error\": 1}\n",
+            "  ],\n",
+            "  \"other\": {\"keys\": 1, \"count\": 1, \"percent\": 12.5},\n",
+            "  \"total\": 8,\n",
+            "  \"keys\": 3,\n",
+            "  \"keys_estimated\": false,\n",
+            "  \"summary\": {\"examined\": 10, \"matched\": 8, \"histogram_keys\": 3, \"distinct_keys\": 3, ",
+            "\"distinct_estimated\": false, \"runtime_ms\": 1.50}\n",
+            "}\n",
+        );
+        assert_eq!(String::from_utf8_lossy(buf.get_ref()), expected);
+    }
+
+    #[test]
+    fn write_json_raw_keys_and_statistics() {
+        let s = Settings::new(args!["--output=json", "--offset=1"]);
+        let h = HistogramWriter::new(s);
+
+        let mut counts: Vec<Pair> = vec![Pair::new(2, "x"), Pair::new(1, "tab\there")];
+        let mut stats = Statistics::new();
+        stats.insert(1.0);
+        stats.insert(2.0);
+        let mut buf = io::Cursor::new(Vec::new());
+        h.write_json(&mut buf, &mut counts, None, &Summary::default(), Some(&stats))
+            .unwrap();
+
+        let json = String::from_utf8_lossy(buf.get_ref());
+        assert!(
+            json.contains("{\"rank\": 2, \"key\": \"tab\\there\", \"count\": 1, \"percent\": 33.3333}\n"),
+            "{}",
+            json
+        );
+        assert!(
+            json.contains("\"statistics\": {\"count\": 2, \"min\": 1, \"max\": 2, \"mean\": 1.5"),
+            "{}",
+            json
+        );
+        assert!(json.ends_with("\"exact\": true}\n}\n"), "{}", json);
+    }
+
+    #[test]
+    fn write_json_breakdown() {
+        let s = Settings::new(args!["--output=json", "--stack"]);
+        let h = HistogramWriter::new(s);
+
+        let (mut counts, breakdown, summary) = StackTokenizer::new("").count("GET 200\nGET 404\nPOST 200\n".as_bytes());
+        let mut buf = io::Cursor::new(Vec::new());
+        h.write_json(&mut buf, &mut counts, Some(&breakdown), &summary, None).unwrap();
+
+        let json = String::from_utf8_lossy(buf.get_ref());
+        assert!(
+            json.contains("\"key\": \"GET\", \"count\": 2, \"percent\": 66.6667, \"breakdown\": {\"200\": 1, \"404\": 1}}"),
+            "{}",
+            json
+        );
+        assert!(json.contains("\"breakdown\": {\"200\": 1}}"), "{}", json);
+    }
+
+    #[test]
+    fn write_csv() {
+        let s = Settings::new(args!["--output=csv", "--header", "--cumulative", "--height=2", "--other"]);
+        let h = HistogramWriter::new(s);
+
+        let mut counts: Vec<Pair> = vec![Pair::new(1, "x"), Pair::new(4, "a,b"), Pair::new(3, "say \"hi\"")];
+        let mut buf = io::Cursor::new(Vec::new());
+        h.write_delimited(&mut buf, &mut counts).unwrap();
+
+        let expected = concat!(
+            "key,count,percent,cumulative_percent,other\n",
+            "\"a,b\",4,50,50,false\n",
+            "\"say \"\"hi\"\"\",3,37.5,87.5,false\n",
+            "(other 1 keys),1,12.5,100,true\n",
+        );
+        assert_eq!(String::from_utf8_lossy(buf.get_ref()), expected);
+    }
+
+    #[test]
+    fn write_csv_other_key() {
+        let s = Settings::new(args!["--output=csv", "--height=1", "--other"]);
+        let h = HistogramWriter::new(s);
+
+        let mut counts: Vec<Pair> = vec![Pair::new(2, "(other 1 keys)"), Pair::new(1, "x")];
+        let mut buf = io::Cursor::new(Vec::new());
+        h.write_delimited(&mut buf, &mut counts).unwrap();
+
+        let expected = concat!("(other 1 keys),2,66.6667,false\n", "(other 1 keys),1,33.3333,true\n");
+        assert_eq!(String::from_utf8_lossy(buf.get_ref()), expected);
+    }
+
+    #[test]
+    fn write_tsv() {
+        let s = Settings::new(args!["--output=tsv"]);
+        let h = HistogramWriter::new(s);
+
+        let mut counts: Vec<Pair> = vec![Pair::new(1, "a\tb"), Pair::new(2, "c,d")];
+        let mut buf = io::Cursor::new(Vec::new());
+        h.write_delimited(&mut buf, &mut counts).unwrap();
+
+        assert_eq!(String::from_utf8_lossy(buf.get_ref()), "c,d\t2\t66.6667\na\\tb\t1\t33.3333\n");
+    }
+
+    #[test]
+    fn block_bar() {
+        assert_eq!(HistogramWriter::block_bar(8, 4, 8), "████");
+        assert_eq!(HistogramWriter::block_bar(8, 4, 3), "█▌");
+        assert_eq!(HistogramWriter::block_bar(64, 1, 1), "▏");
+        assert_eq!(HistogramWriter::block_bar(8, 4, 0), "");
+    }
+
+    #[test]
+    fn write_markdown() {
+        let s = Settings::new(args!["--output=markdown"]);
+        let h = HistogramWriter::new(s);
+
+        let mut counts: Vec<Pair> = vec![Pair::new(1, "a|b"), Pair::new(3, "c")];
+        let mut buf = io::Cursor::new(Vec::new());
+        h.write_markdown(&mut buf, &mut counts).unwrap();
+
+        let expected = concat!(
+            "| Key | Count | Percent | |\n",
+            "|--:|--:|--:|:--|\n",
+            "| c | 3 | 75.00% | ████████████████████ |\n",
+            "| a\\|b | 1 | 25.00% | ██████▋ |\n",
+        );
+        assert_eq!(String::from_utf8_lossy(buf.get_ref()), expected);
+    }
+
+    #[test]
+    fn write_markdown_width_raw() {
+        let s = Settings::new(args!["--output=markdown", "--width=40", "--raw"]);
+        let h = HistogramWriter::new(s);
+
+        let mut counts: Vec<Pair> = vec![Pair::new(1, "a\nb"), Pair::new(2, "c")];
+        let mut buf = io::Cursor::new(Vec::new());
+        h.write_markdown(&mut buf, &mut counts).unwrap();
+
+        let expected = concat!(
+            "| Key | Count | Percent | |\n",
+            "|--:|--:|--:|:--|\n",
+            "| c | 2 | 66.67% | ██████████ |\n",
+            "| a\\nb | 1 | 33.33% | █████ |\n",
+        );
+        assert_eq!(String::from_utf8_lossy(buf.get_ref()), expected);
+    }
+
+    #[test]
+    fn write_markdown_cumulative() {
+        let s = Settings::new(args!["--output=markdown", "--cumulative", "--height=1", "--other"]);
+        let h = HistogramWriter::new(s);
+
+        let mut counts: Vec<Pair> = vec![Pair::new(1, "\u{1b}[0m"), Pair::new(3, "c")];
+        let mut buf = io::Cursor::new(Vec::new());
+        h.write_markdown(&mut buf, &mut counts).unwrap();
+
+        let expected = concat!(
+            "| Key | Count | Percent | Cumulative | |\n",
+            "|--:|--:|--:|--:|:--|\n",
+            "| c | 3 | 75.00% | 75.00% | ████████████████████ |\n",
+            "| (other 1 keys) | 1 | 25.00% | 100.00% | ██████▋ |\n",
+        );
+        assert_eq!(String::from_utf8_lossy(buf.get_ref()), expected);
+    }
+
+    #[test]
+    fn css_colours() {
+        assert_eq!(css_colour("34").as_deref(), Some("#0000c0"));
+        assert_eq!(css_colour("1;92").as_deref(), Some("#40e040"));
+        assert_eq!(css_colour("0"), None);
+        assert_eq!(css_colour("x"), None);
+    }
+
+    #[test]
+    fn css_colours_256_and_truecolor() {
+        assert_eq!(css_colour("38;5;1").as_deref(), Some("#c00000"));
+        assert_eq!(css_colour("38;5;208").as_deref(), Some("#ff8700"));
+        assert_eq!(css_colour("38;5;244").as_deref(), Some("#808080"));
+        assert_eq!(css_colour("1;38;2;0;114;178").as_deref(), Some("#0072b2"));
+    }
+
+    #[test]
+    fn write_svg() {
+        let s = Settings::new(args!["--output=svg", "--width=30", "--title=<Codes>", "--palette=0,37,34,33,31"]);
+        let h = HistogramWriter::new(s);
+
+        let mut counts: Vec<Pair> = vec![Pair::new(1, "a&b"), Pair::new(3, "c")];
+        let mut buf = io::Cursor::new(Vec::new());
+        h.write_svg(&mut buf, &mut counts).unwrap();
+
+        let expected = concat!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"260\" height=\"80\" font-family=\"monospace\" ",
+            "font-size=\"13\" fill=\"#000000\">\n",
+            "<title>&lt;Codes&gt;</title>\n",
+            "<text x=\"10\" y=\"24\" font-weight=\"bold\">&lt;Codes&gt;</text>\n",
+            "<text x=\"34\" y=\"44\" text-anchor=\"end\" fill=\"#c0c0c0\">c</text>\n",
+            "<rect x=\"42\" y=\"33\" width=\"120\" height=\"14\" fill=\"#c00000\"/>\n",
+            "<text x=\"170\" y=\"44\"><tspan fill=\"#0000c0\">3</tspan> <tspan fill=\"#c0a000\">(75.00%)</tspan></text>\n",
+            "<text x=\"34\" y=\"64\" text-anchor=\"end\" fill=\"#c0c0c0\">a&amp;b</text>\n",
+            "<rect x=\"42\" y=\"53\" width=\"40\" height=\"14\" fill=\"#c00000\"/>\n",
+            "<text x=\"90\" y=\"64\"><tspan fill=\"#0000c0\">1</tspan> <tspan fill=\"#c0a000\">(25.00%)</tspan></text>\n",
+            "</svg>\n",
+        );
+        assert_eq!(String::from_utf8_lossy(buf.get_ref()), expected);
+    }
+
+    #[test]
+    fn write_html() {
+        let s = Settings::new(args!["--output=html"]);
+        let h = HistogramWriter::new(s);
+
+        let mut counts: Vec<Pair> = vec![Pair::new(2, "\u{1b}[2J")];
+        let mut buf = io::Cursor::new(Vec::new());
+        h.write_html(&mut buf, &mut counts).unwrap();
+
+        let html = String::from_utf8_lossy(buf.get_ref());
+        assert!(html.starts_with("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Histogram</title>\n"));
+        assert!(html.contains("<body>\n<svg "), "{}", html);
+        // default palette
+        assert!(html.contains("fill=\"#0000c0\"/>"), "{}", html);
+        // control characters are never valid in XML, so keys are escaped even with --raw
+        assert!(html.contains(">\\x1b[2J</text>"), "{}", html);
+        assert!(html.ends_with("</svg>\n</body>\n</html>\n"));
+    }
+
+    #[test]
+    fn write_openmetrics() {
+        let s = Settings::new(args!["--output=openmetrics", "--height=1", "--other"]);
+        let h = HistogramWriter::new(s);
+
+        let mut counts: Vec<Pair> = vec![Pair::new(1, "(other)"), Pair::new(3, "say \"hi\"\\")];
+        let mut buf = io::Cursor::new(Vec::new());
+        h.write_openmetrics(&mut buf, &mut counts).unwrap();
+
+        let expected = concat!(
+            "# TYPE distribution_keys gauge\n",
+            "# HELP distribution_keys Number of lines counted for each key.\n",
+            "distribution_keys{key=\"say \\\"hi\\\"\\\\\"} 3\n",
+            "# TYPE distribution_keys_other gauge\n",
+            "# HELP distribution_keys_other Number of lines counted for the keys not listed.\n",
+            "distribution_keys_other 1\n",
+            "# EOF\n",
+        );
+        assert_eq!(String::from_utf8_lossy(buf.get_ref()), expected);
+    }
+
+    #[test]
+    fn write_openmetrics_every_key() {
+        let s = Settings::new(args!["--output=openmetrics"]);
+        let h = HistogramWriter::new(s);
+
+        let mut counts: Vec<Pair> = (0..40).map(|i| Pair::new(1, &i.to_string())).collect();
+        let mut buf = io::Cursor::new(Vec::new());
+        h.write_openmetrics(&mut buf, &mut counts).unwrap();
+
+        let output = String::from_utf8_lossy(buf.get_ref()).to_string();
+        assert_eq!(output.lines().filter(|l| l.starts_with("distribution_keys{")).count(), 40);
+    }
+
+    #[test]
+    fn write_openmetrics_counter() {
+        let s = Settings::new(args![
+            "--output=openmetrics",
+            "--metric-type=counter",
+            "--metric-name=http_requests_total",
+            "--metric-label=status"
+        ]);
+        let h = HistogramWriter::new(s);
+
+        let mut counts: Vec<Pair> = vec![Pair::new(5, "200"), Pair::new(2, "404")];
+        let mut buf = io::Cursor::new(Vec::new());
+        h.write_openmetrics(&mut buf, &mut counts).unwrap();
+
+        let expected = concat!(
+            "# TYPE http_requests counter\n",
+            "# HELP http_requests Number of lines counted for each status.\n",
+            "http_requests_total{status=\"200\"} 5\n",
+            "http_requests_total{status=\"404\"} 2\n",
+            "# EOF\n",
+        );
+        assert_eq!(String::from_utf8_lossy(buf.get_ref()), expected);
+    }
+
+    #[test]
+    fn write_vertical() {
+        let s = Settings::new(args!["--orientation=vertical", "--height=4", "--width=20"]);
+        let h = HistogramWriter::new(s);
+
+        let mut counts: Vec<Pair> = vec![Pair::new(1, "c"), Pair::new(4, "a"), Pair::new(2, "bb")];
+        let mut buf = io::Cursor::new(Vec::new());
+        h.write_histogram(&mut buf, &mut counts).unwrap();
+
+        let expected = concat!("4|-\n", " |-\n", " |- -\n", " |- - -\n", " +------\n", "  a b c\n", "    b\n",);
+        assert_eq!(String::from_utf8_lossy(buf.get_ref()), expected);
+    }
+
+    #[test]
+    fn write_vertical_partial_blocks() {
+        let s = Settings::new(args!["--orientation=vertical", "--char=pb", "--height=2", "--sort=input"]);
+        let h = HistogramWriter::new(s);
+
+        let mut counts: Vec<Pair> = vec![Pair::new(16, "a"), Pair::new(3, "b"), Pair::new(10, "c")];
+        let mut buf = io::Cursor::new(Vec::new());
+        h.write_histogram(&mut buf, &mut counts).unwrap();
+
+        let expected = concat!("16|█   ▂\n", "  |█ ▃ █\n", "  +------\n", "   a b c\n");
+        assert_eq!(String::from_utf8_lossy(buf.get_ref()), expected);
+    }
+
+    #[test]
+    fn write_vertical_fits_width() {
+        let s = Settings::new(args!["--orientation=vertical", "--height=1", "--width=9", "--key-width=3"]);
+        let h = HistogramWriter::new(s);
+
+        let mut counts: Vec<Pair> = vec![Pair::new(3, "[0, 10)"), Pair::new(2, "日本"), Pair::new(1, "x")];
+        let mut buf = io::Cursor::new(Vec::new());
+        h.write_histogram(&mut buf, &mut counts).unwrap();
+
+        let expected = concat!("3|- - -\n", " +------\n", "  [ 日x\n", "  0 …\n", "  …\n");
+        assert_eq!(String::from_utf8_lossy(buf.get_ref()), expected);
+    }
+
+    #[test]
+    fn write_sparkline() {
+        let s = Settings::new(args!["--output=sparkline", "--sort=input"]);
+        let h = HistogramWriter::new(s);
+
+        let mut counts: Vec<Pair> = vec![Pair::new(1, "a"), Pair::new(8, "b"), Pair::new(4, "c"), Pair::new(2, "d")];
+        let mut buf = io::Cursor::new(Vec::new());
+        h.write_sparkline(&mut buf, &mut counts).unwrap();
+
+        assert_eq!(String::from_utf8_lossy(buf.get_ref()), "▁█▄▂\n");
+    }
+
+    #[test]
+    fn write_sparkline_annotated() {
+        let s = Settings::new(args!["--output=sparkline", "--annotate", "--width=8"]);
+        let h = HistogramWriter::new(s);
+
+        let mut counts: Vec<Pair> = (1..=10).map(|n| Pair::new(n, &n.to_string())).collect();
+        let mut buf = io::Cursor::new(Vec::new());
+        h.write_sparkline(&mut buf, &mut counts).unwrap();
+
+        // ranked largest first, with room for two keys between the counts
+        assert_eq!(String::from_utf8_lossy(buf.get_ref()), "9 █▁ 10\n");
+    }
+
+    #[test]
+    fn write_sparkline_flat() {
+        let s = Settings::new(args!["--output=sparkline", "--annotate"]);
+        let h = HistogramWriter::new(s);
+
+        let mut counts: Vec<Pair> = vec![Pair::new(3, "a"), Pair::new(3, "b")];
+        let mut buf = io::Cursor::new(Vec::new());
+        h.write_sparkline(&mut buf, &mut counts).unwrap();
+
+        assert_eq!(String::from_utf8_lossy(buf.get_ref()), "3 ▄▄ 3\n");
+    }
 }
@@ -0,0 +1,122 @@
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+// 2^14 one-byte registers: 16KiB of memory for a standard error of about 0.8%
+const PRECISION: u32 = 14;
+const REGISTERS: usize = 1 << PRECISION;
+
+/// Estimates the number of distinct keys seen in fixed memory (Flajolet et al.).
+///
+/// Each key is hashed to pick a register, which remembers the longest run of leading zero bits
+/// seen in the rest of the hash. Small cardinalities fall back to linear counting of the empty
+/// registers, as in the HyperLogLog++ paper.
+#[derive(Clone, Debug)]
+pub struct HyperLogLog {
+    registers: Vec<u8>,
+}
+
+impl Default for HyperLogLog {
+    fn default() -> HyperLogLog {
+        HyperLogLog::new()
+    }
+}
+
+impl HyperLogLog {
+    pub fn new() -> HyperLogLog {
+        HyperLogLog {
+            registers: vec![0; REGISTERS],
+        }
+    }
+
+    pub fn insert(&mut self, key: &str) {
+        // SipHash with fixed keys: slower than FxHash, but its high bits are well mixed for short
+        // keys, which the register index and rank depend on
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        let hash = hasher.finish();
+
+        let index = (hash >> (64 - PRECISION)) as usize;
+        // the sentinel bit caps the rank for hashes whose remaining bits are all zero
+        let rest = (hash << PRECISION) | (1 << (PRECISION - 1));
+        let rank = rest.leading_zeros() as u8 + 1;
+        if rank > self.registers[index] {
+            self.registers[index] = rank;
+        }
+    }
+
+    /// Combines the keys seen by another estimator into this one.
+    pub fn merge(&mut self, other: &HyperLogLog) {
+        for (r, &o) in self.registers.iter_mut().zip(other.registers.iter()) {
+            if o > *r {
+                *r = o;
+            }
+        }
+    }
+
+    pub fn estimate(&self) -> u64 {
+        let m = REGISTERS as f64;
+        let alpha = 0.7213 / (1.0 + 1.079 / m);
+        let sum: f64 = self.registers.iter().map(|&r| 2f64.powi(-i32::from(r))).sum();
+        let raw = alpha * m * m / sum;
+
+        let zeros = self.registers.iter().filter(|&&r| r == 0).count();
+        if raw <= 2.5 * m && zeros > 0 {
+            (m * (m / zeros as f64).ln()).round() as u64
+        } else {
+            raw.round() as u64
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::HyperLogLog;
+
+    fn relative_error(estimate: u64, actual: u64) -> f64 {
+        (estimate as f64 - actual as f64).abs() / actual as f64
+    }
+
+    #[test]
+    fn empty() {
+        assert_eq!(HyperLogLog::new().estimate(), 0);
+    }
+
+    #[test]
+    fn duplicates_are_not_counted() {
+        let mut h = HyperLogLog::new();
+        for _ in 0..1000 {
+            h.insert("a");
+            h.insert("b");
+        }
+        assert_eq!(h.estimate(), 2);
+    }
+
+    #[test]
+    fn small_cardinality() {
+        let mut h = HyperLogLog::new();
+        for i in 0..1000 {
+            h.insert(&format!("key-{}", i));
+        }
+        assert!(relative_error(h.estimate(), 1000) < 0.02);
+    }
+
+    #[test]
+    fn large_cardinality() {
+        let mut h = HyperLogLog::new();
+        for i in 0..200_000 {
+            h.insert(&format!("{}", i));
+        }
+        assert!(relative_error(h.estimate(), 200_000) < 0.03);
+    }
+
+    #[test]
+    fn merge() {
+        let mut a = HyperLogLog::new();
+        let mut b = HyperLogLog::new();
+        for i in 0..30_000 {
+            a.insert(&format!("{}", i));
+            b.insert(&format!("{}", i + 20_000));
+        }
+        a.merge(&b);
+        assert!(relative_error(a.estimate(), 50_000) < 0.03);
+    }
+}
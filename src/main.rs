@@ -1,33 +1,209 @@
+mod compare;
+mod escape;
 mod histogram;
+mod hyperloglog;
+mod numeric;
 mod pairlist;
 mod settings;
+mod spacesaving;
+mod stacked;
+mod statistics;
+mod summary;
+mod timestamp;
 mod tokenizer;
+mod truncate;
+mod width;
 
 use std::env;
-use std::io;
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::time::Instant;
 
+use compare::Delta;
 use histogram::HistogramWriter;
-use settings::{PreTallied, Settings};
-use tokenizer::Tokenizer;
-use tokenizer::{LineTokenizer, PreTalliedTokenizer, RegexTokenizer};
+use numeric::NumericTokenizer;
+use pairlist::Pair;
+use settings::{Binning, OutputFormat, PreTallied, Settings, TimeFormat};
+use spacesaving::SpaceSaving;
+use stacked::StackTokenizer;
+use statistics::Statistics;
+use summary::Summary;
+use timestamp::TimeTokenizer;
+use tokenizer::{Counts, LineCounter, Tally, Tokenizer};
+use tokenizer::{LineTokenizer, ParallelTokenizer, PreTalliedTokenizer, RegexTokenizer};
+
+fn count<L: LineCounter, T: io::BufRead>(s: &Settings, counter: L, reader: T) -> (Vec<Pair>, Summary) {
+    let t = ParallelTokenizer::new(counter, s.threads());
+    if s.approx_keys() > 0 {
+        let tally = t.count(reader, SpaceSaving::new(s.approx_keys()));
+        let summary = tally.summary();
+        (tally.into_pairs(), summary)
+    } else {
+        let tally = t.count(reader, Counts::default());
+        let summary = tally.summary();
+        (tally.into_pairs(), summary)
+    }
+}
+
+// lines, or tokens with --tokenize
+fn tally<T: io::BufRead>(s: &Settings, reader: T) -> (Vec<Pair>, Summary) {
+    if s.tokenize() != "" {
+        count(s, RegexTokenizer::new(s.tokenize(), s.match_regexp()), reader)
+    } else {
+        count(s, LineTokenizer::new(r"."), reader)
+    }
+}
+
+fn open(path: &str) -> BufReader<File> {
+    BufReader::new(File::open(path).unwrap_or_else(|e| panic!("Unable to open {}: {}", path, e)))
+}
+
+// the summary and statistics describe the current input rather than the baseline
+fn compare<T: io::BufRead>(s: &Settings, reader: T, stats: &mut Statistics) -> (Vec<Delta>, Summary) {
+    let (before, _) = read(s, open(&s.compare()[0]), &mut Statistics::new());
+    let (after, summary) = match s.compare().get(1) {
+        Some(path) => read(s, open(path), stats),
+        None => read(s, reader, stats),
+    };
+    (compare::compare(before, after), summary)
+}
+
+fn timed<T: io::BufRead>(s: &Settings, reader: T) -> (Vec<Pair>, Summary) {
+    let t = TimeTokenizer::new(s.time_format(), s.interval());
+    let (buckets, examined) = t.read_buckets(reader);
+    let (p, left_out) = t.pairs(&buckets);
+    if left_out {
+        eprintln!("too many intervals between the first and last timestamp, leaving out empty intervals");
+    }
+    let summary = Summary::new(examined, buckets.values().sum(), p.len() as u64);
+    (p, summary)
+}
+
+// pre-tallied input has one pair per line
+fn pre_tallied(p: Vec<Pair>, stats: &mut Statistics) -> (Vec<Pair>, Summary) {
+    for pair in &p {
+        stats.insert(pair.value() as f64);
+    }
+    let matched = p.iter().map(Pair::value).sum();
+    let summary = Summary::new(p.len() as u64, matched, p.len() as u64);
+    (p, summary)
+}
+
+// with --stats, lines that are numbers are summarised as if read with --bins
+fn numeric_keys(p: &[Pair], stats: &mut Statistics) {
+    for pair in p {
+        if let Ok(v) = pair.key().trim().parse::<f64>() {
+            if v.is_finite() {
+                for _ in 0..pair.value() {
+                    stats.insert(v);
+                }
+            }
+        }
+    }
+}
+
+fn numeric<T: io::BufRead>(s: &Settings, reader: T, stats: &mut Statistics) -> (Vec<Pair>, Summary) {
+    let t = NumericTokenizer::new(s.binning(), s.height());
+    let (values, examined) = t.read_values(reader);
+    for &v in &values {
+        stats.insert(v);
+    }
+    let p = t.bin(&values);
+    let summary = Summary::new(examined, values.len() as u64, p.len() as u64);
+    (p, summary)
+}
+
+// pre-tallied pairs, bins, time intervals, or else lines or tokens, as the settings ask
+fn read<T: io::BufRead>(s: &Settings, reader: T, stats: &mut Statistics) -> (Vec<Pair>, Summary) {
+    if s.graph_values() == &PreTallied::ValueKey {
+        pre_tallied(PreTalliedTokenizer::value_key_tokenizer().tokenize(reader), stats)
+    } else if s.graph_values() == &PreTallied::KeyValue {
+        pre_tallied(PreTalliedTokenizer::key_value_tokenizer().tokenize(reader), stats)
+    } else if s.binning() != &Binning::NA {
+        numeric(s, reader, stats)
+    } else if s.time_format() != &TimeFormat::NA {
+        timed(s, reader)
+    } else {
+        let (p, summary) = tally(s, reader);
+        if s.statistics() {
+            numeric_keys(&p, stats);
+        }
+        (p, summary)
+    }
+}
 
 fn main() {
     env_logger::init();
+    let start = Instant::now();
     let s = Settings::new(env::args());
     let stdin = io::stdin();
     let stdin_lock = stdin.lock();
-    let mut p = if s.graph_values() == &PreTallied::ValueKey {
-        PreTalliedTokenizer::value_key_tokenizer().tokenize(stdin_lock)
-    } else if s.graph_values() == &PreTallied::KeyValue {
-        PreTalliedTokenizer::key_value_tokenizer().tokenize(stdin_lock)
-    } else if s.tokenize() != "" {
-        RegexTokenizer::new(s.tokenize(), s.match_regexp()).tokenize(stdin_lock)
+    let mut stats = Statistics::new();
+    let mut breakdown = None;
+    let mut deltas = None;
+    let (mut p, summary) = if !s.compare().is_empty() {
+        let (d, summary) = compare(&s, stdin_lock, &mut stats);
+        deltas = Some(d);
+        (Vec::new(), summary)
+    } else if let Some(matcher) = s.stack() {
+        let (p, b, summary) = StackTokenizer::new(matcher).count(stdin_lock);
+        breakdown = Some(b);
+        (p, summary)
     } else {
-        LineTokenizer::new(r".").tokenize(stdin_lock)
+        read(&s, stdin_lock, &mut stats)
     };
 
+    let verbose = s.verbose();
+    let statistics = s.statistics();
+    let output_format = *s.output_format();
     let h = HistogramWriter::new(s);
+    // the keys of --compare are those of both inputs, which the estimate doesn't cover
+    let h = if summary.distinct_estimated() && deltas.is_none() {
+        h.with_distinct_estimate(summary.distinct())
+    } else {
+        h
+    };
+    if output_format == OutputFormat::Json {
+        let stats = if statistics && stats.count() > 0 { Some(&stats) } else { None };
+        h.write_json(
+            &mut io::stdout().lock(),
+            &mut p,
+            breakdown.as_ref(),
+            &summary.with_runtime(start.elapsed()),
+            stats,
+        )
+        .expect("Unable to write JSON to STDOUT");
+        return;
+    }
+    if verbose {
+        h.write_summary(&mut io::stderr(), &summary.with_runtime(start.elapsed()))
+            .expect("Unable to write summary to STDERR");
+    }
+
     let stdout = io::stdout();
     let mut handle = stdout.lock();
-    h.write_histogram(&mut handle, &mut p).expect("Unable to write histogram to STDOUT");
+    match (output_format, breakdown, deltas) {
+        (OutputFormat::Csv | OutputFormat::Tsv, _, _) => h.write_delimited(&mut handle, &mut p).expect("Unable to write rows to STDOUT"),
+        (OutputFormat::Markdown, _, _) => h.write_markdown(&mut handle, &mut p).expect("Unable to write table to STDOUT"),
+        (OutputFormat::Svg, _, _) => h.write_svg(&mut handle, &mut p).expect("Unable to write chart to STDOUT"),
+        (OutputFormat::Html, _, _) => h.write_html(&mut handle, &mut p).expect("Unable to write chart to STDOUT"),
+        (OutputFormat::Sparkline, _, _) => h.write_sparkline(&mut handle, &mut p).expect("Unable to write sparkline to STDOUT"),
+        (OutputFormat::OpenMetrics, _, _) => h.write_openmetrics(&mut handle, &mut p).expect("Unable to write metrics to STDOUT"),
+        (_, _, Some(mut d)) => h
+            .write_comparison(&mut handle, &mut d)
+            .expect("Unable to write histogram to STDOUT"),
+        (_, Some(b), _) => {
+            h.write_stacked_histogram(&mut handle, &mut p, &b)
+                .expect("Unable to write histogram to STDOUT");
+            h.write_legend(&mut io::stderr(), &b).expect("Unable to write legend to STDERR");
+        }
+        (_, None, None) => h.write_histogram(&mut handle, &mut p).expect("Unable to write histogram to STDOUT"),
+    }
+
+    if statistics && stats.count() > 0 {
+        h.write_statistics(&mut io::stderr(), &stats)
+            .expect("Unable to write statistics to STDERR");
+    } else if statistics {
+        eprintln!("--stats found no numbers to summarise");
+    }
 }
@@ -0,0 +1,448 @@
+use std::io;
+
+use super::pairlist::Pair;
+use super::settings::{BinRule, Binning};
+use super::tokenizer::{for_each_line, Tokenizer};
+
+// most bins a histogram of numbers may have, so a bin width far smaller than the spread of the
+// values fails with a message rather than running out of memory
+const MAX_BINS: usize = 100_000;
+
+// an f64 holds no more than 17 significant digits
+const MAX_DIGITS: i32 = 17;
+
+/// Reads one number per line and counts how many fall into each bin.
+///
+/// Bins are returned in ascending order, including empty ones, labelled `[lo, hi)`. Lines that
+/// are not numbers are skipped.
+pub struct NumericTokenizer<'a> {
+    binning: &'a Binning,
+    max_bins: usize,
+}
+
+impl<'a> NumericTokenizer<'a> {
+    /// `max_bins` limits how many bins an automatic binning rule may choose.
+    pub fn new(binning: &'a Binning, max_bins: usize) -> NumericTokenizer<'a> {
+        NumericTokenizer { binning, max_bins }
+    }
+
+    /// Returns the numbers in the input along with the number of lines examined.
+    pub fn read_values<T: io::BufRead>(&self, reader: T) -> (Vec<f64>, u64) {
+        let mut values = Vec::new();
+        let mut examined = 0;
+        for_each_line(reader, |line| {
+            examined += 1;
+            if let Ok(v) = line.trim().parse::<f64>() {
+                if v.is_finite() {
+                    values.push(v)
+                }
+            }
+        });
+
+        (values, examined)
+    }
+
+    pub fn bin(&self, values: &[f64]) -> Vec<Pair> {
+        if values.is_empty() {
+            return Vec::new();
+        }
+
+        let min = values.iter().cloned().fold(f64::INFINITY, f64::min);
+        let max = values.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+
+        match self.binning {
+            Binning::NA => panic!("numeric input needs a binning"),
+            Binning::Count(n) => Bins::spanning(min, max, *n).count(values),
+            Binning::Width(w) => Bins::aligned(min, max, *w).count(values),
+            Binning::Edges(edges) => Bins::new(edges.clone()).count(values),
+            Binning::Log(base) => Bins::powers(min, max, *base).count(values),
+            Binning::Auto(rule) => {
+                let bins = NumericTokenizer::bin_count(rule, values, max - min).clamp(1, self.max_bins.max(1));
+                Bins::nice(min, max, bins).count(values)
+            }
+        }
+    }
+
+    // number of bins a rule calls for, falling back to Sturges' rule when the data has no spread
+    fn bin_count(rule: &BinRule, values: &[f64], range: f64) -> usize {
+        let n = values.len() as f64;
+        let sturges = n.log2().ceil() as usize + 1;
+        let width = match rule {
+            BinRule::Sturges => return sturges,
+            BinRule::Scott => {
+                let mean = values.iter().sum::<f64>() / n;
+                let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n;
+                3.49 * variance.sqrt() / n.cbrt()
+            }
+            BinRule::FreedmanDiaconis => {
+                let mut sorted = values.to_vec();
+                sorted.sort_by(f64::total_cmp);
+                2.0 * (quantile(&sorted, 0.75) - quantile(&sorted, 0.25)) / n.cbrt()
+            }
+        };
+
+        if width > 0.0 {
+            (range / width).ceil() as usize
+        } else {
+            sturges
+        }
+    }
+}
+
+/// The `q`th quantile of sorted values, interpolating linearly between the closest ranks.
+pub fn quantile(sorted: &[f64], q: f64) -> f64 {
+    let rank = q * (sorted.len() - 1) as f64;
+    let lo = rank.floor() as usize;
+    let hi = rank.ceil() as usize;
+    sorted[lo] + (sorted[hi] - sorted[lo]) * (rank - lo as f64)
+}
+
+impl<'a> Tokenizer for NumericTokenizer<'a> {
+    fn tokenize<T: io::BufRead>(&self, reader: T) -> Vec<Pair> {
+        let (values, _) = self.read_values(reader);
+        self.bin(&values)
+    }
+}
+
+// bins between consecutive edges, plus underflow and overflow bins for values outside them
+struct Bins {
+    edges: Vec<f64>,
+    // whether the last bin includes its upper edge
+    closed: bool,
+}
+
+impl Bins {
+    fn new(edges: Vec<f64>) -> Bins {
+        Bins { edges, closed: false }
+    }
+
+    fn spanning(min: f64, max: f64, n: usize) -> Bins {
+        let n = if min == max { 1 } else { n.max(1) };
+        assert!(n <= MAX_BINS, "Too many bins: {} is more than the limit of {}", n, MAX_BINS);
+        let width = (max - min) / n as f64;
+        let edges = (0..=n).map(|i| if i == n { max } else { min + width * i as f64 }).collect();
+        Bins { edges, closed: true }
+    }
+
+    // no more than `bins` bins aligned to a round width of 1, 2 or 5 times a power of ten
+    fn nice(min: f64, max: f64, bins: usize) -> Bins {
+        if min == max {
+            return Bins::spanning(min, max, 1);
+        }
+
+        let mut exponent = ((max - min) / bins as f64).log10().floor() as i32;
+        loop {
+            for step in [1.0, 2.0, 5.0] {
+                let b = Bins::aligned(min, max, step * 10f64.powi(exponent));
+                if b.edges.len() - 1 <= bins {
+                    return b;
+                }
+            }
+            exponent += 1;
+        }
+    }
+
+    fn aligned(min: f64, max: f64, width: f64) -> Bins {
+        let (lo, hi) = ((min / width).floor(), (max / width).floor());
+        assert!(
+            hi - lo < MAX_BINS as f64,
+            "Too many bins: a width of {} from {} to {} needs more than the limit of {}",
+            format_number(width),
+            format_number(min),
+            format_number(max),
+            MAX_BINS
+        );
+        let edge = |i: i64| i as f64 * width;
+        let first = floor_index(min, lo as i64, edge);
+        let last = floor_index(max, hi as i64, edge) + 1;
+        Bins::new((first..=last).map(edge).collect())
+    }
+
+    // non-positive values fall in an underflow bin below the smallest power
+    fn powers(min: f64, max: f64, base: u32) -> Bins {
+        let base = f64::from(base);
+        // the exponents of finite numbers fit in an i32
+        let edge = |i: i64| base.powi(i as i32);
+        let smallest = if min > 0.0 { min } else { 1.0 };
+        let first = floor_index(smallest, smallest.log(base).floor() as i64, edge);
+        let largest = max.max(smallest);
+        let last = floor_index(largest, largest.log(base).floor() as i64, edge) + 1;
+        Bins::new((first..=last).map(edge).collect())
+    }
+
+    fn index(&self, value: f64) -> Option<usize> {
+        let last = self.edges.len() - 1;
+        if self.closed && value == self.edges[last] {
+            Some(last - 1)
+        } else if value < self.edges[0] || value >= self.edges[last] {
+            None
+        } else {
+            Some(self.edges.partition_point(|&e| e <= value) - 1)
+        }
+    }
+
+    fn count(&self, values: &[f64]) -> Vec<Pair> {
+        let bins = self.edges.len() - 1;
+        let mut counts = vec![0; bins];
+        let (mut under, mut over) = (0, 0);
+        for &v in values {
+            match self.index(v) {
+                Some(i) => counts[i] += 1,
+                None if v < self.edges[0] => under += 1,
+                None => over += 1,
+            }
+        }
+
+        // enough digits that neighbouring edges are told apart
+        let digits = (6..MAX_DIGITS)
+            .find(|&d| {
+                self.edges
+                    .windows(2)
+                    .all(|e| format_significant(e[0], d) != format_significant(e[1], d))
+            })
+            .unwrap_or(MAX_DIGITS);
+        let label = |edge: f64| format_significant(edge, digits);
+
+        let last = self.edges.len() - 1;
+        let mut pairs = Vec::with_capacity(bins + 2);
+        if under > 0 {
+            pairs.push(Pair::new(under, &format!("(-inf, {})", label(self.edges[0]))));
+        }
+        for (i, &count) in counts.iter().enumerate() {
+            let lo = label(self.edges[i]);
+            let hi = label(self.edges[i + 1]);
+            let close = if self.closed && i == bins - 1 { ']' } else { ')' };
+            pairs.push(Pair::new(count, &format!("[{}, {}{}", lo, hi, close)));
+        }
+        if over > 0 {
+            pairs.push(Pair::new(over, &format!("[{}, inf)", label(self.edges[last]))));
+        }
+        pairs
+    }
+}
+
+// the index of the edge at or below value, correcting an estimate thrown off by rounding
+fn floor_index<F: Fn(i64) -> f64>(value: f64, estimate: i64, edge: F) -> i64 {
+    let mut i = estimate;
+    while edge(i) > value {
+        i -= 1;
+    }
+    while edge(i + 1) <= value {
+        i += 1;
+    }
+    i
+}
+
+/// Formats a number with up to six significant digits, dropping trailing zeros.
+pub fn format_number(n: f64) -> String {
+    format_significant(n, 6)
+}
+
+fn format_significant(n: f64, digits: i32) -> String {
+    let magnitude = if n == 0.0 { 0 } else { n.abs().log10().floor() as i32 };
+    let decimals = (digits - 1 - magnitude).clamp(0, digits + 6) as usize;
+    let s = format!("{:.*}", decimals, n);
+    let s = if s.contains('.') {
+        s.trim_end_matches('0').trim_end_matches('.')
+    } else {
+        &s
+    };
+    if s == "-0" {
+        String::from("0")
+    } else {
+        String::from(s)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{format_number, quantile, NumericTokenizer};
+    use crate::pairlist::Pair;
+    use crate::settings::{BinRule, Binning};
+    use crate::tokenizer::Tokenizer;
+    use std::io;
+
+    fn bin(binning: Binning, input: &str) -> Vec<Pair> {
+        NumericTokenizer::new(&binning, 15).tokenize(io::Cursor::new(input))
+    }
+
+    #[test]
+    fn read_values_skips_non_numbers() {
+        let t = NumericTokenizer::new(&Binning::Count(2), 15);
+        let c = io::Cursor::new("1\n x\n2.5 \nNaN\n-3e2\n");
+        assert_eq!(t.read_values(c), (vec![1.0, 2.5, -300.0], 5));
+    }
+
+    #[test]
+    fn empty_input() {
+        assert_eq!(bin(Binning::Count(3), ""), vec![]);
+    }
+
+    #[test]
+    fn count_bins() {
+        assert_eq!(
+            bin(Binning::Count(3), "0\n1\n2\n3\n4\n5\n6\n9\n"),
+            vec![Pair::new(3, "[0, 3)"), Pair::new(3, "[3, 6)"), Pair::new(2, "[6, 9]")]
+        );
+    }
+
+    #[test]
+    fn count_bins_single_value() {
+        assert_eq!(bin(Binning::Count(4), "7\n7\n"), vec![Pair::new(2, "[7, 7]")]);
+    }
+
+    #[test]
+    fn width_bins() {
+        assert_eq!(
+            bin(Binning::Width(10.0), "3\n12\n35\n"),
+            vec![
+                Pair::new(1, "[0, 10)"),
+                Pair::new(1, "[10, 20)"),
+                Pair::new(0, "[20, 30)"),
+                Pair::new(1, "[30, 40)")
+            ]
+        );
+    }
+
+    #[test]
+    fn narrow_bins_are_labelled_apart() {
+        assert_eq!(
+            bin(Binning::Width(0.0000001), "1\n1.00000015\n"),
+            vec![Pair::new(1, "[1, 1.0000001)"), Pair::new(1, "[1.0000001, 1.0000002)")]
+        );
+    }
+
+    #[test]
+    fn large_values_in_narrow_bins() {
+        assert_eq!(
+            bin(Binning::Width(1.0), "10000000000\n10000000001\n"),
+            vec![
+                Pair::new(1, "[10000000000, 10000000001)"),
+                Pair::new(1, "[10000000001, 10000000002)")
+            ]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Too many bins")]
+    fn too_many_width_bins() {
+        bin(Binning::Width(1.0), "5\n10000000000\n");
+    }
+
+    #[test]
+    #[should_panic(expected = "Too many bins")]
+    fn too_many_count_bins() {
+        bin(Binning::Count(1_000_000_000), "0\n1\n");
+    }
+
+    #[test]
+    fn fractional_width_bins() {
+        assert_eq!(
+            bin(Binning::Width(0.1), "0.25\n0.31\n"),
+            vec![Pair::new(1, "[0.2, 0.3)"), Pair::new(1, "[0.3, 0.4)")]
+        );
+    }
+
+    #[test]
+    fn edge_bins() {
+        assert_eq!(
+            bin(Binning::Edges(vec![0.0, 10.0, 100.0]), "-1\n5\n10\n50\n100\n"),
+            vec![
+                Pair::new(1, "(-inf, 0)"),
+                Pair::new(1, "[0, 10)"),
+                Pair::new(2, "[10, 100)"),
+                Pair::new(1, "[100, inf)"),
+            ]
+        );
+    }
+
+    #[test]
+    fn log_bins() {
+        assert_eq!(
+            bin(Binning::Log(2), "0\n1\n3\n4\n9\n"),
+            vec![
+                Pair::new(1, "(-inf, 1)"),
+                Pair::new(1, "[1, 2)"),
+                Pair::new(1, "[2, 4)"),
+                Pair::new(1, "[4, 8)"),
+                Pair::new(1, "[8, 16)"),
+            ]
+        );
+    }
+
+    #[test]
+    fn log10_bins() {
+        assert_eq!(
+            bin(Binning::Log(10), "0.5\n20\n"),
+            vec![Pair::new(1, "[0.1, 1)"), Pair::new(0, "[1, 10)"), Pair::new(1, "[10, 100)")]
+        );
+    }
+
+    #[test]
+    fn log10_bins_exact_power() {
+        assert_eq!(
+            bin(Binning::Log(10), "100\n1000\n"),
+            vec![Pair::new(1, "[100, 1000)"), Pair::new(1, "[1000, 10000)")]
+        );
+    }
+
+    fn values(n: usize) -> String {
+        (0..n).map(|i| format!("{}\n", (i * 37) % 100)).collect()
+    }
+
+    #[test]
+    fn sturges_bins() {
+        // 200 values call for 9 bins, rounded to a width of 20
+        let actual = bin(Binning::Auto(BinRule::Sturges), &values(200));
+        assert_eq!(actual.len(), 5);
+        assert_eq!(actual[0], Pair::new(40, "[0, 20)"));
+        assert_eq!(actual.iter().map(Pair::value).sum::<u64>(), 200);
+    }
+
+    #[test]
+    fn scott_bins() {
+        let actual = bin(Binning::Auto(BinRule::Scott), &values(1000));
+        assert_eq!(actual.len(), 10);
+        assert_eq!(actual[9], Pair::new(100, "[90, 100)"));
+    }
+
+    #[test]
+    fn freedman_diaconis_bins() {
+        let actual = bin(Binning::Auto(BinRule::FreedmanDiaconis), &values(1000));
+        assert_eq!(actual.len(), 10);
+        assert_eq!(actual[0], Pair::new(100, "[0, 10)"));
+    }
+
+    #[test]
+    fn auto_bins_fit_height() {
+        let t = NumericTokenizer::new(&Binning::Auto(BinRule::FreedmanDiaconis), 3);
+        let actual = t.tokenize(io::Cursor::new(values(1000)));
+        assert_eq!(actual.len(), 2);
+        assert_eq!(actual[1], Pair::new(500, "[50, 100)"));
+    }
+
+    #[test]
+    fn auto_bins_no_spread() {
+        assert_eq!(bin(Binning::Auto(BinRule::Scott), "4\n4\n4\n"), vec![Pair::new(3, "[4, 4]")]);
+    }
+
+    #[test]
+    fn quantiles() {
+        let sorted = [1.0, 2.0, 3.0, 4.0];
+        assert_eq!(quantile(&sorted, 0.0), 1.0);
+        assert_eq!(quantile(&sorted, 0.5), 2.5);
+        assert_eq!(quantile(&sorted, 1.0), 4.0);
+        assert_eq!(quantile(&[7.0], 0.9), 7.0);
+    }
+
+    #[test]
+    fn format_numbers() {
+        assert_eq!(format_number(0.0), "0");
+        assert_eq!(format_number(-0.0), "0");
+        assert_eq!(format_number(10.0), "10");
+        assert_eq!(format_number(0.30000000000000004), "0.3");
+        assert_eq!(format_number(1234567.0), "1234567");
+        assert_eq!(format_number(-2.5), "-2.5");
+        assert_eq!(format_number(0.000123456789), "0.000123457");
+    }
+}
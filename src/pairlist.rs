@@ -5,13 +5,20 @@ use std::cmp::Ordering;
 pub struct Pair {
     value: u64,
     key: String,
+    error: u64,
 }
 
 impl Pair {
     pub fn new(value: u64, key: &str) -> Pair {
+        Pair::approximate(value, 0, key)
+    }
+
+    /// A pair whose value may overstate the true count by up to `error`.
+    pub fn approximate(value: u64, error: u64, key: &str) -> Pair {
         Pair {
             value,
             key: key.to_string(),
+            error,
         }
     }
 
@@ -19,6 +26,10 @@ impl Pair {
         self.value
     }
 
+    pub fn error(&self) -> u64 {
+        self.error
+    }
+
     pub fn key(&self) -> &str {
         &self.key
     }
@@ -34,13 +45,50 @@ impl Ord for Pair {
     fn cmp(&self, other: &Pair) -> Ordering {
         let value = self.value.cmp(&other.value);
         let key = self.key.cmp(&other.key);
-        value.then(key)
+        // the error only breaks ties, so that Ord agrees with the derived Eq
+        value.then(key).then(self.error.cmp(&other.error))
+    }
+}
+
+/// Compares keys in natural order: runs of digits compare by their numeric value and everything
+/// else character by character, so "item2" sorts before "item10".
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a, b);
+    loop {
+        match (a.chars().next(), b.chars().next()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let (digits_a, rest_a) = a.split_at(a.find(|c: char| !c.is_ascii_digit()).unwrap_or(a.len()));
+                let (digits_b, rest_b) = b.split_at(b.find(|c: char| !c.is_ascii_digit()).unwrap_or(b.len()));
+                let (value_a, value_b) = (digits_a.trim_start_matches('0'), digits_b.trim_start_matches('0'));
+                // more significant digits is a larger number; leading zeros only break ties
+                let order = value_a
+                    .len()
+                    .cmp(&value_b.len())
+                    .then(value_a.cmp(value_b))
+                    .then(digits_a.len().cmp(&digits_b.len()));
+                if order != Ordering::Equal {
+                    return order;
+                }
+                a = rest_a;
+                b = rest_b;
+            }
+            (Some(x), Some(y)) => {
+                if x != y {
+                    return x.cmp(&y);
+                }
+                a = &a[x.len_utf8()..];
+                b = &b[y.len_utf8()..];
+            }
+        }
     }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::pairlist::Pair;
+    use crate::pairlist::{natural_cmp, Pair};
     use std::cmp::Ordering;
 
     #[test]
@@ -52,6 +100,7 @@ mod test {
         assert_eq!(x.cmp(&x), Ordering::Equal);
         assert_eq!(x.cmp(&y), Ordering::Less);
         assert_eq!(x.cmp(&z), Ordering::Less);
+        assert_eq!(x.cmp(&Pair::approximate(1, 1, "a")), Ordering::Less);
     }
 
     #[test]
@@ -68,4 +117,13 @@ mod test {
         assert_eq!(vec[1], Pair::new(1, "ba"));
         assert_eq!(vec[2], Pair::new(1, "aa"));
     }
+
+    #[test]
+    fn natural_order() {
+        let mut keys = ["10", "9", "item10", "item2", "item02", "", "b", "a1b", "a1a", "100"];
+        keys.sort_by(|a, b| natural_cmp(a, b));
+
+        assert_eq!(keys, ["", "9", "10", "100", "a1a", "a1b", "b", "item2", "item02", "item10"]);
+        assert_eq!(natural_cmp("x7", "x7"), Ordering::Equal);
+    }
 }
@@ -3,6 +3,9 @@ use std::ffi::OsStr;
 use std::fs::File;
 use std::io::{self, BufRead, BufReader};
 use std::process;
+use std::thread;
+
+use unicode_segmentation::UnicodeSegmentation;
 
 #[derive(Debug, PartialEq)]
 pub enum PreTallied {
@@ -17,6 +20,140 @@ impl Default for PreTallied {
     }
 }
 
+/// How numeric input is grouped into bins.
+#[derive(Debug, Default, PartialEq)]
+pub enum Binning {
+    #[default]
+    NA,
+    /// this many equal-width bins spanning the range of the input
+    Count(usize),
+    /// bins of this width, aligned to multiples of it
+    Width(f64),
+    /// bins between consecutive edges
+    Edges(Vec<f64>),
+    /// bins between consecutive powers of this base
+    Log(u32),
+    /// equal-width bins sized by a rule, no more than the height of the histogram
+    Auto(BinRule),
+}
+
+/// Rules for choosing the number of bins from the input.
+#[derive(Debug, PartialEq)]
+pub enum BinRule {
+    /// log2(n) + 1 bins, for roughly normal data
+    Sturges,
+    /// bins of width 3.49σ/∛n
+    Scott,
+    /// bins of width 2·IQR/∛n, robust to outliers
+    FreedmanDiaconis,
+}
+
+/// How timestamps at the start of each line are parsed.
+#[derive(Debug, Default, PartialEq)]
+pub enum TimeFormat {
+    #[default]
+    NA,
+    /// a date with optional time and UTC offset, e.g. 2012-01-01T08:01:52+01:00
+    Iso8601,
+    /// Jan  1 08:01:52, in the current year
+    Syslog,
+    /// seconds since the Unix epoch
+    Epoch,
+    /// milliseconds since the Unix epoch
+    EpochMillis,
+    /// a strftime-style format
+    Strftime(String),
+}
+
+/// Format the histogram is written in.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum OutputFormat {
+    /// a table with bars, for the terminal
+    #[default]
+    Text,
+    /// one JSON document with the rows, totals and summary
+    Json,
+    /// comma-separated rows, quoted as in RFC 4180
+    Csv,
+    /// tab-separated rows, with tabs, newlines and backslashes in keys escaped
+    Tsv,
+    /// a GitHub-flavoured markdown table with a column of block bars
+    Markdown,
+    /// a standalone SVG bar chart
+    Svg,
+    /// an HTML page holding the SVG chart
+    Html,
+    /// a metric family in the OpenMetrics text format, labelled by key
+    OpenMetrics,
+    /// a single line of block glyphs, one per key
+    Sparkline,
+}
+
+/// Palettes selectable with `--theme`, in the order of `--palette`: regular, key, count, percent and graph.
+const THEMES: [(&str, &str); 5] = [
+    ("default", "0,0,32,35,34"),
+    ("mono", "0,1,0,2,0"),
+    ("ocean", "0,colour117,colour39,colour75,colour27"),
+    ("heat", "0,colour220,colour208,colour214,colour196"),
+    // Okabe and Ito's palette, which stays distinguishable with the common colour vision deficiencies
+    ("colourblind", "0,#56b4e9,#e69f00,#cc79a7,#0072b2"),
+];
+
+const COLOUR_NAMES: [&str; 8] = ["black", "red", "green", "yellow", "blue", "magenta", "cyan", "white"];
+
+// eighths of a block drawn by --char=pb, growing rightwards along bars and upwards up columns
+pub const HORIZONTAL_BLOCKS: [char; 8] = ['▏', '▎', '▍', '▌', '▋', '▊', '▉', '█'];
+pub const VERTICAL_BLOCKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Direction the bars of the text histogram are drawn in.
+#[derive(Debug, Default, PartialEq)]
+pub enum Orientation {
+    /// a row per key, with bars growing to the right
+    #[default]
+    Horizontal,
+    /// a column per key, with bars growing up from an axis and keys written downwards beneath it
+    Vertical,
+}
+
+/// Type of the metric family written by `--output=openmetrics`.
+#[derive(Debug, Default, PartialEq)]
+pub enum MetricType {
+    #[default]
+    Gauge,
+    Counter,
+}
+
+/// How keys too long for the key column are shortened.
+#[derive(Debug, Default, PartialEq)]
+pub enum Truncation {
+    /// cut off the end
+    #[default]
+    End,
+    /// cut out the middle, keeping the start and the end
+    Middle,
+    /// abbreviate and then drop leading directories, keeping the file name
+    Path,
+}
+
+/// Order of the rows in the histogram.
+#[derive(Debug, Default, PartialEq)]
+pub enum SortOrder {
+    /// highest count first
+    #[default]
+    Count,
+    /// lowest count first
+    CountAscending,
+    /// keys in lexicographic order
+    Key,
+    /// keys in natural order, comparing runs of digits by their numeric value
+    Natural,
+    /// the order the tokenizer produced them in, e.g. bin order or first appearance
+    Input,
+    /// largest change from the --compare baseline first
+    Change,
+}
+
+#[allow(dead_code)]
 #[derive(Debug, Default)]
 pub struct Settings {
     program_name: String,
@@ -53,6 +190,39 @@ pub struct Settings {
     graph_chars: Vec<char>,
     partial_blocks: Vec<String>,
     partial_lines: Vec<String>,
+    threads: usize,
+    approx_keys: usize,
+    binning: Binning,
+    sort_order: SortOrder,
+    sort_arg: Option<SortOrder>,
+    statistics: bool,
+    time_format: TimeFormat,
+    interval: u64,
+    stack: Option<String>,
+    compare: Vec<String>,
+    other: bool,
+    totals: bool,
+    cumulative: bool,
+    cutoff: f64,
+    offset: usize,
+    bottom: usize,
+    min_count: u64,
+    max_count: u64,
+    key_width: usize,
+    truncation: Truncation,
+    min_bar: usize,
+    raw: bool,
+    output_format: OutputFormat,
+    header: bool,
+    title: Option<String>,
+    metric_name: String,
+    metric_label: String,
+    metric_type: MetricType,
+    orientation: Orientation,
+    annotate: bool,
+    theme: Option<String>,
+    themes: Vec<(String, String)>,
+    palette: Vec<String>,
 }
 
 impl Settings {
@@ -65,6 +235,11 @@ impl Settings {
         self.height
     }
 
+    /// Whether the number of rows was given with --height or --limit rather than left at its default.
+    pub fn height_given(&self) -> bool {
+        self.height_arg != 0
+    }
+
     pub fn graph_values(&self) -> &PreTallied {
         &self.graph_values
     }
@@ -89,8 +264,8 @@ impl Settings {
         self.histogram_char.as_str()
     }
 
-    pub fn unicode_mode(&self) -> bool {
-        self.unicode_mode
+    pub fn colourised_output(&self) -> bool {
+        self.colourised_output
     }
 
     pub fn regular_colour(&self) -> &str {
@@ -113,6 +288,148 @@ impl Settings {
         self.graph_colour.as_str()
     }
 
+    pub fn verbose(&self) -> bool {
+        self.verbose
+    }
+
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+
+    pub fn approx_keys(&self) -> usize {
+        self.approx_keys
+    }
+
+    pub fn binning(&self) -> &Binning {
+        &self.binning
+    }
+
+    pub fn sort_order(&self) -> &SortOrder {
+        &self.sort_order
+    }
+
+    pub fn statistics(&self) -> bool {
+        self.statistics
+    }
+
+    pub fn time_format(&self) -> &TimeFormat {
+        &self.time_format
+    }
+
+    /// Width of time buckets in seconds.
+    pub fn interval(&self) -> u64 {
+        self.interval
+    }
+
+    /// Regex splitting lines into primary and secondary keys for stacked bars; empty to split on
+    /// whitespace.
+    pub fn stack(&self) -> Option<&str> {
+        self.stack.as_deref()
+    }
+
+    /// Whether keys beyond the height of the histogram are added up into an "(other N keys)" row.
+    pub fn other(&self) -> bool {
+        self.other
+    }
+
+    pub fn totals(&self) -> bool {
+        self.totals
+    }
+
+    /// Rows to skip from the top, or from the bottom with `bottom`.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Number of rows to show from the bottom rather than the top; zero to show the top.
+    pub fn bottom(&self) -> usize {
+        self.bottom
+    }
+
+    pub fn min_count(&self) -> u64 {
+        self.min_count
+    }
+
+    /// Largest count of a row to show; zero for no limit.
+    pub fn max_count(&self) -> u64 {
+        self.max_count
+    }
+
+    /// Widest the key column may be; zero for as wide as the longest key.
+    pub fn key_width(&self) -> usize {
+        self.key_width
+    }
+
+    pub fn output_format(&self) -> &OutputFormat {
+        &self.output_format
+    }
+
+    /// Whether CSV and TSV output starts with a row naming the columns
+    pub fn header(&self) -> bool {
+        self.header
+    }
+
+    /// Heading for SVG and HTML charts
+    pub fn title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+
+    /// Whether sparklines are labelled with the smallest and largest counts
+    pub fn annotate(&self) -> bool {
+        self.annotate
+    }
+
+    pub fn orientation(&self) -> &Orientation {
+        &self.orientation
+    }
+
+    /// Name of the `--output=openmetrics` metric family
+    pub fn metric_name(&self) -> &str {
+        self.metric_name.as_str()
+    }
+
+    /// Name of the label holding each key in `--output=openmetrics`
+    pub fn metric_label(&self) -> &str {
+        self.metric_label.as_str()
+    }
+
+    pub fn metric_type(&self) -> &MetricType {
+        &self.metric_type
+    }
+
+    /// SGR parameters, such as "32" or "38;5;208", for regular text, keys, counts, percentages and bars
+    pub fn palette(&self) -> &[String] {
+        &self.palette
+    }
+
+    /// Whether keys are printed as they are rather than with control characters escaped.
+    pub fn raw(&self) -> bool {
+        self.raw
+    }
+
+    pub fn truncation(&self) -> &Truncation {
+        &self.truncation
+    }
+
+    /// Narrowest the bars may be before long keys are shortened.
+    pub fn min_bar(&self) -> usize {
+        self.min_bar
+    }
+
+    pub fn cumulative(&self) -> bool {
+        self.cumulative
+    }
+
+    /// Cumulative percentage at which to stop showing keys, instead of at the height; zero for none.
+    pub fn cutoff(&self) -> f64 {
+        self.cutoff
+    }
+
+    /// The baseline file, optionally followed by a file to read instead of STDIN.
+    pub fn compare(&self) -> &[String] {
+        &self.compare
+    }
+
     pub fn new<I>(args: I) -> Settings
     where
         I: Iterator<Item = String>,
@@ -123,8 +440,13 @@ impl Settings {
             match_regexp: String::from(r"."),
             width: 80,
             height: 15,
-            colour_palette: String::from("0,0,32,35,34"),
+            colour_palette: String::from(THEMES[0].1),
+            metric_name: String::from("distribution_keys"),
+            metric_label: String::from("key"),
             histogram_char: String::from("-"),
+            threads: 1,
+            interval: 3600,
+            min_bar: 10,
             ..Default::default()
         };
 
@@ -161,6 +483,28 @@ impl Settings {
                 process::exit(1);
             } else if arg == "-c" || arg == "--color" {
                 s.colourised_output = true;
+            } else if arg == "-v" || arg == "--verbose" {
+                s.verbose = true;
+            } else if arg == "--stats" {
+                s.statistics = true;
+            } else if arg == "--time" {
+                s.time_format = TimeFormat::Iso8601;
+            } else if arg == "--annotate" {
+                s.annotate = true;
+            } else if arg == "--header" {
+                s.header = true;
+            } else if arg == "--raw" {
+                s.raw = true;
+            } else if arg == "--cumulative" {
+                s.cumulative = true;
+            } else if arg == "--other" {
+                s.other = true;
+            } else if arg == "--totals" {
+                s.totals = true;
+            } else if arg == "--by-change" {
+                s.sort_arg = Some(SortOrder::Change);
+            } else if arg == "--stack" {
+                s.stack = Some(String::new());
             } else if arg == "-g" || arg == "--graph" {
                 // can pass --graph without option, will default to value/key ordering
                 // since unix perfers that for piping-to-sort reasons
@@ -183,13 +527,131 @@ impl Settings {
                     }
                 } else if v[0] == "-p" || v[0] == "--palette" {
                     s.colour_palette = String::from(v[1]);
+                    s.theme = None;
+                    s.colourised_output = true;
+                } else if v[0] == "--theme" {
+                    s.theme = Some(String::from(v[1]));
                     s.colourised_output = true;
+                } else if v[0] == "--define-theme" {
+                    let (name, palette) = v[1].split_once('=').expect("Invalid theme: expected NAME=PALETTE");
+                    s.themes.push((String::from(name), String::from(palette)));
                 } else if v[0] == "-s" || v[0] == "--size" {
                     s.size = String::from(v[1])
                 } else if v[0] == "-t" || v[0] == "--tokenize" {
                     s.tokenize = String::from(v[1])
                 } else if v[0] == "-m" || v[0] == "--match" {
                     s.match_regexp = String::from(v[1])
+                } else if v[0] == "--threads" {
+                    s.threads = v[1].parse::<usize>().unwrap();
+                } else if v[0] == "--approx" {
+                    s.approx_keys = v[1].parse::<usize>().unwrap();
+                } else if v[0] == "--bins" {
+                    s.binning = match v[1] {
+                        "log2" => Binning::Log(2),
+                        "log10" => Binning::Log(10),
+                        "sturges" => Binning::Auto(BinRule::Sturges),
+                        "scott" => Binning::Auto(BinRule::Scott),
+                        "auto" | "fd" | "freedman-diaconis" => Binning::Auto(BinRule::FreedmanDiaconis),
+                        e if e.contains(',') => Binning::Edges(Settings::parse_edges(e)),
+                        n => Binning::Count(n.parse::<usize>().unwrap()),
+                    }
+                } else if v[0] == "--time" {
+                    s.time_format = match v[1] {
+                        "iso8601" | "iso" => TimeFormat::Iso8601,
+                        "syslog" => TimeFormat::Syslog,
+                        "epoch" => TimeFormat::Epoch,
+                        "epoch-ms" => TimeFormat::EpochMillis,
+                        f if f.contains('%') => TimeFormat::Strftime(String::from(f)),
+                        _ => panic!("Invalid time format"),
+                    }
+                } else if v[0] == "--output" {
+                    s.output_format = match v[1] {
+                        "text" => OutputFormat::Text,
+                        "json" => OutputFormat::Json,
+                        "csv" => OutputFormat::Csv,
+                        "tsv" => OutputFormat::Tsv,
+                        "markdown" | "md" => OutputFormat::Markdown,
+                        "svg" => OutputFormat::Svg,
+                        "html" => OutputFormat::Html,
+                        "openmetrics" | "prometheus" => OutputFormat::OpenMetrics,
+                        "sparkline" | "spark" => OutputFormat::Sparkline,
+                        _ => panic!("Invalid output format"),
+                    }
+                } else if v[0] == "--key-width" {
+                    s.key_width = v[1].parse::<usize>().unwrap();
+                    assert!(s.key_width > 0, "Invalid key width: must be at least 1");
+                } else if v[0] == "--truncate" {
+                    s.truncation = match v[1] {
+                        "end" => Truncation::End,
+                        "middle" => Truncation::Middle,
+                        "path" => Truncation::Path,
+                        _ => panic!("Invalid truncation"),
+                    }
+                } else if v[0] == "--min-bar" {
+                    s.min_bar = v[1].parse::<usize>().unwrap();
+                } else if v[0] == "--limit" {
+                    s.height_arg = v[1].parse::<usize>().unwrap();
+                } else if v[0] == "--offset" {
+                    s.offset = v[1].parse::<usize>().unwrap();
+                } else if v[0] == "--bottom" {
+                    s.bottom = v[1].parse::<usize>().unwrap();
+                    assert!(s.bottom > 0, "Invalid bottom: must be at least 1");
+                } else if v[0] == "--min-count" {
+                    s.min_count = v[1].parse::<u64>().unwrap();
+                } else if v[0] == "--max-count" {
+                    s.max_count = v[1].parse::<u64>().unwrap();
+                    assert!(s.max_count > 0, "Invalid max-count: must be at least 1");
+                } else if v[0] == "--sort" {
+                    s.sort_arg = Some(match v[1] {
+                        "count" | "count-desc" => SortOrder::Count,
+                        "count-asc" => SortOrder::CountAscending,
+                        "key" => SortOrder::Key,
+                        "natural" | "numeric" => SortOrder::Natural,
+                        "input" => SortOrder::Input,
+                        "change" => SortOrder::Change,
+                        _ => panic!("Invalid sort order"),
+                    })
+                } else if v[0] == "--cutoff" {
+                    s.cutoff = v[1].trim_end_matches('%').parse::<f64>().unwrap();
+                    assert!(s.cutoff > 0.0 && s.cutoff <= 100.0, "Invalid cutoff: must be a percentage");
+                    s.cumulative = true;
+                } else if v[0] == "--compare" {
+                    s.compare = v[1].split(',').map(String::from).collect();
+                    assert!(
+                        s.compare.len() <= 2,
+                        "Invalid compare: expected a baseline and at most one other file"
+                    );
+                } else if v[0] == "--orientation" {
+                    s.orientation = match v[1] {
+                        "horizontal" => Orientation::Horizontal,
+                        "vertical" => Orientation::Vertical,
+                        _ => panic!("Invalid orientation"),
+                    }
+                } else if v[0] == "--metric-name" {
+                    assert!(Settings::valid_metric_name(v[1], true), "Invalid metric name");
+                    s.metric_name = String::from(v[1]);
+                } else if v[0] == "--metric-label" {
+                    assert!(
+                        Settings::valid_metric_name(v[1], false) && !v[1].starts_with("__"),
+                        "Invalid label name"
+                    );
+                    s.metric_label = String::from(v[1]);
+                } else if v[0] == "--metric-type" {
+                    s.metric_type = match v[1] {
+                        "gauge" => MetricType::Gauge,
+                        "counter" => MetricType::Counter,
+                        _ => panic!("Invalid metric type"),
+                    }
+                } else if v[0] == "--title" {
+                    s.title = Some(String::from(v[1]));
+                } else if v[0] == "--stack" {
+                    s.stack = Some(String::from(v[1]));
+                } else if v[0] == "--interval" {
+                    s.interval = Settings::parse_interval(v[1]);
+                } else if v[0] == "--bin-width" {
+                    let w = v[1].parse::<f64>().unwrap();
+                    assert!(w > 0.0, "Invalid bin width");
+                    s.binning = Binning::Width(w);
                 }
             }
         }
@@ -215,14 +677,69 @@ impl Settings {
             s.height = s.height_arg;
         }
 
-        // colour palette
+        // bins and time intervals are shown in order rather than by count, unless sorted otherwise
+        if s.binning != Binning::NA || s.time_format != TimeFormat::NA {
+            s.sort_order = SortOrder::Input;
+        }
+        if let Some(order) = s.sort_arg.take() {
+            s.sort_order = order;
+        }
+
+        // first appearance is only known when the input is read in order
+        if s.sort_order == SortOrder::Input {
+            s.threads = 1;
+        }
+
+        assert!(
+            s.compare.is_empty() || s.output_format == OutputFormat::Text,
+            "--compare only supports text output"
+        );
+        assert!(
+            !s.statistics || (s.time_format == TimeFormat::NA && s.stack.is_none()),
+            "--stats doesn't support --time or --stack"
+        );
+        // bins spanning each input's own range wouldn't line up between the baseline and the input
+        assert!(
+            s.compare.is_empty() || !matches!(s.binning, Binning::Count(_) | Binning::Auto(_)),
+            "--compare needs bins that don't depend on the input, such as --bin-width or --bins=<edges>"
+        );
+        assert!(
+            s.orientation == Orientation::Horizontal || (s.compare.is_empty() && s.stack.is_none()),
+            "--orientation=vertical doesn't support --compare or --stack"
+        );
+
+        // zero threads means one per available CPU
+        if s.threads == 0 {
+            s.threads = thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+        }
+
+        // colour palette, from a theme defined in the rcfile or on the command line before a built-in one
+        if let Some(theme) = &s.theme {
+            let defined = s.themes.iter().rev().map(|(name, palette)| (name.as_str(), palette.as_str()));
+            let colourblind = matches!(theme.as_str(), "colorblind" | "color-blind" | "colour-blind");
+            let name = if colourblind { "colourblind" } else { theme.as_str() };
+            let (_, palette) = defined
+                .chain(THEMES)
+                .find(|(n, _)| *n == name)
+                .unwrap_or_else(|| panic!("Unknown theme: {}", theme));
+            s.colour_palette = String::from(palette);
+        }
+        // entries left out keep their default colour. the palette is only parsed when something is drawn
+        // in it, in colour or as a chart, so a bad one can't break plain output
+        let coloured = s.colourised_output || matches!(s.output_format, OutputFormat::Svg | OutputFormat::Html);
+        let palette = if coloured { s.colour_palette.as_str() } else { THEMES[0].1 };
+        let defaults = THEMES[0].1.split(',');
+        let entries = palette.split(',').map(Some).chain(std::iter::repeat(None));
+        s.palette = defaults
+            .zip(entries)
+            .map(|(default, entry)| Settings::parse_colour(entry.filter(|e| !e.is_empty()).unwrap_or(default)))
+            .collect();
         if s.colourised_output {
-            let cl: Vec<&str> = s.colour_palette.splitn(5, ',').collect();
-            s.regular_colour = format!("\u{001b}[{}m", cl[0]);
-            s.key_colour = format!("\u{001b}[{}m", cl[1]);
-            s.ct_colour = format!("\u{001b}[{}m", cl[2]);
-            s.pct_colour = format!("\u{001b}[{}m", cl[3]);
-            s.graph_colour = format!("\u{001b}[{}m", cl[4]);
+            s.regular_colour = format!("\u{001b}[{}m", s.palette[0]);
+            s.key_colour = format!("\u{001b}[{}m", s.palette[1]);
+            s.ct_colour = format!("\u{001b}[{}m", s.palette[2]);
+            s.pct_colour = format!("\u{001b}[{}m", s.palette[3]);
+            s.graph_colour = format!("\u{001b}[{}m", s.palette[4]);
         }
 
         if s.histogram_char == "dt" {
@@ -232,12 +749,15 @@ impl Settings {
 
         if s.histogram_char == "pb" {
             s.char_width = 0.125;
-            s.graph_chars = vec!['▏', '▎', '▍', '▌', '▋', '▊', '▉', '█'];
+            s.graph_chars = match s.orientation {
+                Orientation::Horizontal => HORIZONTAL_BLOCKS.to_vec(),
+                Orientation::Vertical => VERTICAL_BLOCKS.to_vec(),
+            };
         }
 
-        // detect whether the user has passed a multibyte unicode character
-        // directly as the histogram char
-        if s.histogram_char.as_bytes()[0] > 128 {
+        // detect whether the user has passed a single unicode character, which may take
+        // several code points, directly as the histogram char
+        if !s.histogram_char.is_ascii() && s.histogram_char.graphemes(true).count() == 1 {
             s.unicode_mode = true
         }
 
@@ -250,12 +770,34 @@ impl Settings {
         writeln!(writer)?;
         writeln!(writer, "usage: <commandWithOutput> | {}", self.program_name)?;
         writeln!(writer, "         [--size={{sm|med|lg|full}} | --width=<width> --height=<height>]")?;
-        writeln!(writer, "         [--color] [--palette=r,k,c,p,g]")?;
+        writeln!(writer, "         [--color] [--palette=r,k,c,p,g] [--theme=T] [--define-theme=NAME=P]")?;
+        writeln!(writer, "         [--orientation=horizontal|vertical]")?;
         writeln!(writer, "         [--tokenize=<tokenChar>]")?;
         writeln!(writer, "         [--graph[=[kv|vk]] [--numonly[=derivative,diff|abs,absolute,actual]]")?;
-        writeln!(writer, "         [--char=<barChars>|<substitutionString>]")?;
-        writeln!(writer, "         [--help] [--verbose]")?;
+        writeln!(writer, "         [--char=<barChars>|<substitutionString>] [--threads=<threads>]")?;
+        writeln!(writer, "         [--approx=<keys>] [--bins=<bins> | --bin-width=<width>]")?;
+        writeln!(writer, "         [--time[=<format>] [--interval=<interval>]] [--stack[=<regex>]]")?;
+        writeln!(writer, "         [--compare=<baseline>[,<file>] [--by-change]]")?;
+        writeln!(writer, "         [--cumulative] [--cutoff=<percent>] [--sort=<order>]")?;
+        writeln!(writer, "         [--key-width=<width>] [--truncate=end|middle|path] [--min-bar=<width>] [--raw]")?;
+        writeln!(writer, "         [--offset=<rows>] [--limit=<rows> | --bottom=<rows>] [--min-count=<n>] [--max-count=<n>]")?;
+        writeln!(writer, "         [--output=text|json|csv|tsv|markdown|svg|html|openmetrics|sparkline]")?;
+        writeln!(writer, "         [--header] [--title=T] [--annotate]")?;
+        writeln!(writer, "         [--metric-name=N] [--metric-label=L] [--metric-type=gauge|counter]")?;
+        writeln!(writer, "         [--other] [--totals] [--help] [--verbose] [--stats]")?;
         writeln!(writer, "  --keys=K       every {} values added, prune hash to K keys (default 5000)", self.key_prune_interval)?;
+        writeln!(writer, "  --annotate     label --output=sparkline with its smallest and largest counts")?;
+        writeln!(writer, "  --approx=K     approximate counts keeping only K keys in memory, marking counts that may be")?;
+        writeln!(writer, "                 overstated with ~. keys more frequent than 1/K of the input are always kept")?;
+        writeln!(writer, "  --bins=B       input is numbers, one per line. graph how many fall in each bin, in bin order:")?;
+        writeln!(writer, "        N        N bins of equal width spanning the input")?;
+        writeln!(writer, "        E,E,...  bins between each pair of increasing edges")?;
+        writeln!(writer, "        log2     bins between successive powers of 2 (log10 for powers of 10)")?;
+        writeln!(writer, "        sturges  choose the bin width by Sturges' rule, with at most --height bins")?;
+        writeln!(writer, "        scott    choose the bin width by Scott's rule, with at most --height bins")?;
+        writeln!(writer, "        fd       choose the bin width by the Freedman-Diaconis rule (synonymous with auto)")?;
+        writeln!(writer, "  --bin-width=W  input is numbers, graph bins of width W")?;
+        writeln!(writer, "  --bottom=N     show the last N rows rather than the first, e.g. the rarest keys")?;
         writeln!(writer, "  --char=C       character(s) to use for histogram character, some substitutions follow:")?;
         writeln!(writer, "        pl       Use 1/3-width unicode partial lines to simulate 3x actual terminal width")?;
         writeln!(writer, "        pb       Use 1/8-width unicode partial blocks to simulate 8x actual terminal width")?;
@@ -267,31 +809,96 @@ impl Settings {
         writeln!(writer, "        dt       (•) Dot")?;
         writeln!(writer, "        sq       (□) Square")?;
         writeln!(writer, "  --color        colourise the output")?;
+        writeln!(writer, "  --cumulative   add a column with the running total percentage")?;
+        writeln!(writer, "  --cutoff=P     show keys until they add up to P percent of the total rather than stopping")?;
+        writeln!(writer, "                 at the height. implies --cumulative")?;
+        writeln!(writer, "  --define-theme=NAME=P")?;
+        writeln!(writer, "                 name palette P for --theme, e.g. in the rcfile")?;
         writeln!(writer, "  --graph[=G]    input is already key/value pairs. vk is default:")?;
         writeln!(writer, "        kv       input is ordered key then value")?;
         writeln!(writer, "        vk       input is ordered value then key")?;
+        writeln!(writer, "  --header       start --output=csv or tsv with a row naming the columns")?;
         writeln!(writer, "  --height=N     height of histogram, headers non-inclusive, overrides --size")?;
         writeln!(writer, "  --help         get help")?;
+        writeln!(writer, "  --key-width=N  shorten keys longer than N characters, marking where with …")?;
+        writeln!(writer, "  --limit=N      show at most N rows, same as --height")?;
         writeln!(writer, "  --logarithmic  logarithmic graph")?;
         writeln!(writer, "  --match=RE     only match lines (or tokens) that match this regexp, some substitutions follow:")?;
         writeln!(writer, "        word     ^[A-Z,a-z]+\\$ - tokens/lines must be entirely alphabetic")?;
         writeln!(writer, "        num      ^\\d+\\$        - tokens/lines must be entirely numeric")?;
+        writeln!(writer, "  --min-bar=N    shorten long keys so bars are at least N characters wide (default 10)")?;
+        writeln!(writer, "  --min-count=N  only show rows counted at least N times")?;
+        writeln!(writer, "  --max-count=N  only show rows counted at most N times")?;
         writeln!(writer, "  --numonly[=N]  input is numerics, simply graph values without labels")?;
         writeln!(writer, "        actual   input is just values (default - abs, absolute are synonymous to actual)")?;
         writeln!(writer, "        diff     input monotonically-increasing, graph differences (of 2nd and later values)")?;
-        writeln!(writer, "  --palette=P    comma-separated list of ANSI colour values for portions of the output")?;
-        writeln!(writer, "                 in this order: regular, key, count, percent, graph. implies --color.")?;
+        writeln!(writer, "  --offset=N     skip the first N rows (or with --bottom, the last N), e.g. --offset=20 --limit=20")?;
+        writeln!(writer, "  --output=F     text (default) or json, a document with each row's rank, key, count and percent,")?;
+        writeln!(writer, "                 the totals, the --verbose summary and any --stats. keys are not escaped")?;
+        writeln!(writer, "        csv      rows of key, count, percent (and cumulative percent with --cumulative), and with")?;
+        writeln!(writer, "                 --other, a true/false column marking the remainder row")?;
+        writeln!(writer, "        tsv      the same separated by tabs, with tabs and newlines in keys written as \\t and \\n")?;
+        writeln!(writer, "        markdown a table of key, count, percent and a bar, for pasting into tickets and wikis")?;
+        writeln!(writer, "                 the bars are a quarter of --width")?;
+        writeln!(writer, "        svg      a standalone bar chart in the --palette colours, --width columns wide")?;
+        writeln!(writer, "        html     a self-contained page holding the svg chart")?;
+        writeln!(writer, "        openmetrics  a metric family with a sample per key, e.g. for the node-exporter textfile")?;
+        writeln!(writer, "                 collector. named by --metric-name (default distribution_keys), with the key in")?;
+        writeln!(writer, "                 the --metric-label label (default key), a gauge or --metric-type=counter.")?;
+        writeln!(writer, "                 every key is written unless limited by --height or --limit")?;
+        writeln!(writer, "        sparkline  one line of ▁▂▃▄▅▆▇█, a glyph per key in --sort order, as many as fit in")?;
+        writeln!(writer, "                 --width. with --annotate, between the smallest and largest counts")?;
+        writeln!(writer, "  --orientation=O")?;
+        writeln!(writer, "                 horizontal (default) bars, or vertical columns --height rows tall with the keys")?;
+        writeln!(writer, "                 written downwards beneath them, as many as fit in --width. suits --bins and --time")?;
+        writeln!(writer, "  --other        add up the keys that don't fit in the histogram into an \"(other N keys)\" row")?;
+        writeln!(writer, "  --palette=P    comma-separated list of colours for portions of the output in this order:")?;
+        writeln!(writer, "                 regular, key, count, percent, graph. each is an ANSI colour value such as 32")?;
+        writeln!(writer, "                 or 1;32, a name such as green or bright-green, colourN for 256-colour index")?;
+        writeln!(writer, "                 N, or #rrggbb. colours left out keep their default. implies --color.")?;
+        writeln!(writer, "  --raw          print keys as they are. by default control characters and escape sequences")?;
+        writeln!(writer, "                 in keys are shown escaped, e.g. \\t or \\x1b, so they can't garble the terminal")?;
         writeln!(writer, "  --rcfile=F     use this rcfile instead of ~/.distributionrc - must be first argument!")?;
+        writeln!(writer, "  --stats        summarise lines that are numbers, numeric input (--bins) or pre-tallied values")?;
+        writeln!(writer, "                 (--graph) on stderr with count, min, max, mean, median, stddev and percentiles.")?;
+        writeln!(writer, "                 percentiles of more than 100,000 values are estimated to within 1% and marked with ~.")?;
+        writeln!(writer, "                 not with --time or --stack")?;
         writeln!(writer, "  --size=S       size of histogram, can abbreviate to single character, overridden by --width/--height")?;
         writeln!(writer, "        small    40x10")?;
         writeln!(writer, "        medium   80x20")?;
         writeln!(writer, "        large    120x30")?;
         writeln!(writer, "        full     terminal width x terminal height (approximately)")?;
+        writeln!(writer, "  --totals       finish with the total count and number of distinct keys on stderr")?;
+        writeln!(writer, "  --theme=T      a named palette: default, mono, ocean, heat, or colourblind. implies --color")?;
+        writeln!(writer, "  --threads=N    count input on N worker threads, 0 for one per CPU (default 1)")?;
+        writeln!(writer, "  --time[=F]     lines start with a timestamp. graph how many fall in each --interval, in order:")?;
+        writeln!(writer, "        iso8601  2012-01-01T08:01:52Z, the time and offset are optional (default)")?;
+        writeln!(writer, "        syslog   Jan  1 08:01:52, in the current year")?;
+        writeln!(writer, "        epoch    seconds since 1970-01-01 UTC (epoch-ms for milliseconds)")?;
+        writeln!(writer, "        %d/%b/%Y:%T %z  any strftime-style format using %Y %y %m %d %e %j %b %H %M %S %f %z %s %T %F")?;
+        writeln!(writer, "  --interval=I   width of --time buckets: minute, hour (default), day, week or N[s|m|h|d|w]")?;
+        writeln!(writer, "  --compare=B    compare the counts in baseline file B against STDIN, or against file F with")?;
+        writeln!(writer, "                 --compare=B,F, showing the change in each key. both are tokenized the same way")?;
+        writeln!(writer, "  --by-change    with --compare, show the largest changes first rather than the largest counts.")?;
+        writeln!(writer, "                 same as --sort=change")?;
+        writeln!(writer, "  --sort=S       order of the rows:")?;
+        writeln!(writer, "        count    highest count first (default)")?;
+        writeln!(writer, "        count-asc lowest count first")?;
+        writeln!(writer, "        key      keys in lexicographic order")?;
+        writeln!(writer, "        natural  keys in natural order, so numbers sort by value: 2 before 10")?;
+        writeln!(writer, "        input    first appearance in the input, reading it on one thread (default with --bins and --time)")?;
+        writeln!(writer, "        change   largest change first, with --compare")?;
+        writeln!(writer, "  --stack[=RE]   stack each bar by a secondary key. the first two capture groups of RE give the")?;
+        writeln!(writer, "                 primary and secondary keys; by default the first two fields of each line")?;
+        writeln!(writer, "  --truncate=T   how to shorten long keys: end (default), middle, or path to abbreviate")?;
+        writeln!(writer, "                 leading directories")?;
+        writeln!(writer, "  --title=T      heading of --output=svg and html charts")?;
         writeln!(writer, "  --tokenize=RE  split input on regexp RE and make histogram of all resulting tokens")?;
         writeln!(writer, "        word     [^\\w] - split on non-word characters like colons, brackets, commas, etc")?;
         writeln!(writer, "        white    \\s    - split on whitespace")?;
         writeln!(writer, "  --width=N      width of the histogram report, N characters, overrides --size")?;
-        writeln!(writer, "  --verbose      be verbose")?;
+        writeln!(writer, "  --verbose      be verbose, summarising the input on stderr. with --approx the number of")?;
+        writeln!(writer, "                 distinct keys is estimated")?;
         writeln!(writer)?;
         writeln!(writer, "You can use single-characters options, like so: -h=25 -w=20 -v. You must still include the =")?;
         writeln!(writer)?;
@@ -302,7 +909,7 @@ impl Settings {
         writeln!(writer, "  zcat /var/log/syslog*gz | awk '{{print $5}}'  | {} --t=word --m-word --h=15 --c=/", self.program_name)?;
         writeln!(writer, "  zcat /var/log/syslog*gz | cut -c 1-9        | {} --width=60 --height=10 --char=em", self.program_name)?;
         writeln!(writer, "  find /etc -type f       | cut -c 6-         | {} --tokenize=/ --w=90 --h=35 --c=dt", self.program_name)?;
-        writeln!(writer, "  cat /usr/share/dict/words | awk '{{print length($1)}}' | {} --c=* --w=50 --h=10 | sort -n", self.program_name)?;
+        writeln!(writer, "  cat /usr/share/dict/words | awk '{{print length($1)}}' | {} --c=* --w=50 --h=10 --sort=natural", self.program_name)?;
         writeln!(writer)?;
         Ok(())
     }
@@ -317,6 +924,70 @@ impl Settings {
         })
     }
 
+    fn parse_edges(edges: &str) -> Vec<f64> {
+        let edges: Vec<f64> = edges.split(',').map(|e| e.trim().parse::<f64>().unwrap()).collect();
+        assert!(edges.windows(2).all(|w| w[0] < w[1]), "Bin edges must be increasing");
+        edges
+    }
+
+    // SGR parameters for a palette entry: raw parameters such as "1;32", a colour name such as "red" or
+    // "bright-red", a 256-colour index such as "colour208", or truecolor "#rrggbb"
+    fn parse_colour(entry: &str) -> String {
+        let entry = entry.trim().to_ascii_lowercase();
+        if entry.chars().all(|c| c.is_ascii_digit() || c == ';') && !entry.is_empty() {
+            return entry;
+        }
+        if let Some(hex) = entry.strip_prefix('#') {
+            let channel = |i: usize| hex.get(i..i + 2).and_then(|c| u8::from_str_radix(c, 16).ok());
+            match (hex.len(), channel(0), channel(2), channel(4)) {
+                (6, Some(r), Some(g), Some(b)) => return format!("38;2;{};{};{}", r, g, b),
+                _ => panic!("Invalid colour: {}", entry),
+            }
+        }
+        if let Some(index) = entry.strip_prefix("colour").or_else(|| entry.strip_prefix("color")) {
+            let index = index.parse::<u8>().unwrap_or_else(|_| panic!("Invalid colour: {}", entry));
+            return format!("38;5;{}", index);
+        }
+        let (base, name) = match entry.strip_prefix("bright-") {
+            Some(name) => (90, name),
+            None => (30, entry.as_str()),
+        };
+        match COLOUR_NAMES.iter().position(|n| *n == name) {
+            Some(i) => (base + i).to_string(),
+            None if entry == "default" => String::from("0"),
+            None => panic!("Invalid colour: {}", entry),
+        }
+    }
+
+    // metric names are letters, digits, underscores and colons, not starting with a digit; label
+    // names are the same without colons
+    fn valid_metric_name(name: &str, colons: bool) -> bool {
+        let allowed = |c: char| c.is_ascii_alphanumeric() || c == '_' || (colons && c == ':');
+        name.chars().all(allowed) && name.chars().next().is_some_and(|c| !c.is_ascii_digit())
+    }
+
+    // minute, hour, day, week, or a number of seconds with an optional s/m/h/d/w unit
+    fn parse_interval(interval: &str) -> u64 {
+        let (n, unit) = match interval {
+            "minute" => ("1", "m"),
+            "hour" => ("1", "h"),
+            "day" => ("1", "d"),
+            "week" => ("1", "w"),
+            i => i.split_at(i.find(|c: char| !c.is_ascii_digit()).unwrap_or(i.len())),
+        };
+        let seconds = match unit {
+            "" | "s" => 1,
+            "m" => 60,
+            "h" => 3600,
+            "d" => 86400,
+            "w" => 604800,
+            _ => panic!("Invalid interval"),
+        };
+        let interval = n.parse::<u64>().unwrap().checked_mul(seconds).expect("Invalid interval");
+        assert!(interval > 0 && interval <= i64::MAX as u64, "Invalid interval");
+        interval
+    }
+
     fn strip_comments(line: &str) -> &str {
         line.split('#').map(|token| token.trim()).next().expect("error parsing line")
     }
@@ -324,7 +995,7 @@ impl Settings {
 
 #[cfg(test)]
 mod test {
-    use super::{PreTallied, Settings};
+    use super::{BinRule, Binning, MetricType, Orientation, OutputFormat, PreTallied, Settings, SortOrder, TimeFormat, Truncation};
 
     #[test]
     fn test_empty_args() {
@@ -409,6 +1080,8 @@ mod test {
     test_option!(short_char, "-c=-", histogram_char, "-");
     test_option!(long_char, "--char=x", histogram_char, "x");
     test_option!(char_dt, "--char=dt", histogram_char, "•", unicode_mode, true);
+    test_option!(char_combining, "--char=e\u{301}", unicode_mode, true);
+    test_option!(char_two_unicode, "--char=→·", unicode_mode, false);
     test_option!(
         char_pb,
         "--char=pb",
@@ -448,6 +1121,60 @@ mod test {
     );
     test_option_fail!(invalid_short_palette, "-p=x");
     test_option_fail!(invalid_long_palette, "--palette=x");
+    test_option!(no_palette, "", palette, vec!["0", "0", "32", "35", "34"], regular_colour, "");
+    test_option!(
+        palette_colours,
+        "--palette=default,bright-cyan,colour208,#FF8000,1;34",
+        palette,
+        vec!["0", "96", "38;5;208", "38;2;255;128;0", "1;34"],
+        ct_colour,
+        "\u{001b}[38;5;208m"
+    );
+    test_option!(
+        short_palette_defaults,
+        "--palette=0,red",
+        palette,
+        vec!["0", "31", "32", "35", "34"]
+    );
+    test_option!(palette_skipped_entry, "--palette=0,,33", palette, vec!["0", "0", "33", "35", "34"]);
+    test_option_fail!(invalid_palette_name, "--palette=0,purple");
+    test_option_fail!(invalid_palette_index, "--palette=0,colour256");
+    test_option_fail!(invalid_palette_hex, "--palette=0,#12345");
+
+    test_option!(no_theme, "", theme, None);
+    test_option!(
+        theme,
+        "--theme=colourblind",
+        palette,
+        vec!["0", "38;2;86;180;233", "38;2;230;159;0", "38;2;204;121;167", "38;2;0;114;178"],
+        colourised_output,
+        true
+    );
+    test_option!(
+        theme_american,
+        "--theme=color-blind",
+        palette,
+        vec!["0", "38;2;86;180;233", "38;2;230;159;0", "38;2;204;121;167", "38;2;0;114;178"]
+    );
+    test_option_fail!(unknown_theme, "--theme=neon");
+
+    #[test]
+    fn define_theme() {
+        let args = vec!["test", "--rcfile=/dev/null", "--theme=mine", "--define-theme=mine=0,blue,yellow"];
+        let s = Settings::new(args.into_iter().map(String::from));
+        assert_eq!(s.palette, vec!["0", "34", "33", "35", "34"]);
+    }
+
+    #[test]
+    fn palette_overrides_theme() {
+        let args = vec!["test", "--rcfile=/dev/null", "--theme=heat", "--palette=0,red"];
+        let s = Settings::new(args.into_iter().map(String::from));
+        assert_eq!(s.palette[1], "31");
+
+        let args = vec!["test", "--rcfile=/dev/null", "--palette=0,red", "--theme=mono"];
+        let s = Settings::new(args.into_iter().map(String::from));
+        assert_eq!(s.palette[1], "1");
+    }
 
     test_option!(short_size_small, "-s=small", size, "small", width, 60, height, 10);
     test_option!(long_size_small, "--size=small", size, "small", width, 60, height, 10);
@@ -468,4 +1195,207 @@ mod test {
 
     test_option!(short_match, "-m=(.)", match_regexp, "(.)");
     test_option!(long_match, "--match=(.)", match_regexp, "(.)");
+
+    test_option!(not_verbose, "", verbose, false);
+    test_option!(short_verbose, "-v", verbose, true);
+    test_option!(long_verbose, "--verbose", verbose, true);
+
+    test_option!(no_time, "", time_format, TimeFormat::NA, interval, 3600);
+    test_option!(time, "--time", time_format, TimeFormat::Iso8601, sort_order, SortOrder::Input);
+    test_option!(time_syslog, "--time=syslog", time_format, TimeFormat::Syslog);
+    test_option!(time_epoch, "--time=epoch", time_format, TimeFormat::Epoch);
+    test_option!(time_epoch_ms, "--time=epoch-ms", time_format, TimeFormat::EpochMillis);
+    test_option!(
+        time_strftime,
+        "--time=%d/%b/%Y",
+        time_format,
+        TimeFormat::Strftime(String::from("%d/%b/%Y"))
+    );
+    test_option_fail!(invalid_time, "--time=yesterday");
+    test_option!(interval_minute, "--interval=minute", interval, 60);
+    test_option!(interval_day, "--interval=day", interval, 86400);
+    test_option!(interval_seconds, "--interval=90", interval, 90);
+    test_option!(interval_hours, "--interval=6h", interval, 21600);
+    test_option!(interval_weeks, "--interval=2w", interval, 1209600);
+    test_option_fail!(invalid_interval, "--interval=5y");
+    test_option_fail!(zero_interval, "--interval=0m");
+    test_option_fail!(interval_too_long, "--interval=9999999999999999w");
+
+    test_option!(no_output, "", output_format, OutputFormat::Text);
+    test_option!(output_text, "--output=text", output_format, OutputFormat::Text);
+    test_option!(output_json, "--output=json", output_format, OutputFormat::Json);
+    test_option!(output_csv, "--output=csv", output_format, OutputFormat::Csv);
+    test_option!(output_tsv, "--output=tsv", output_format, OutputFormat::Tsv);
+    test_option!(output_markdown, "--output=markdown", output_format, OutputFormat::Markdown);
+    test_option!(output_md, "--output=md", output_format, OutputFormat::Markdown);
+    test_option_fail!(invalid_output, "--output=xml");
+    test_option!(output_svg, "--output=svg", output_format, OutputFormat::Svg);
+    test_option!(output_html, "--output=html", output_format, OutputFormat::Html);
+    test_option!(output_sparkline, "--output=sparkline", output_format, OutputFormat::Sparkline);
+    test_option!(no_annotate, "", annotate, false);
+    test_option!(annotate, "--annotate", annotate, true);
+    test_option!(no_header, "", header, false);
+    test_option!(header, "--header", header, true);
+
+    #[test]
+    #[should_panic]
+    fn compare_json() {
+        let args = vec!["test", "--rcfile=/dev/null", "--compare=a", "--output=json"];
+        Settings::new(args.into_iter().map(String::from));
+    }
+
+    #[test]
+    #[should_panic(expected = "--stats doesn't support")]
+    fn stats_time() {
+        let args = vec!["test", "--rcfile=/dev/null", "--stats", "--time=epoch"];
+        Settings::new(args.into_iter().map(String::from));
+    }
+
+    #[test]
+    #[should_panic(expected = "--compare needs bins")]
+    fn compare_bin_count() {
+        let args = vec!["test", "--rcfile=/dev/null", "--compare=a", "--bins=10"];
+        Settings::new(args.into_iter().map(String::from));
+    }
+
+    test_option!(no_raw, "", raw, false);
+    test_option!(raw, "--raw", raw, true);
+
+    test_option!(no_key_width, "", key_width, 0, truncation, Truncation::End, min_bar, 10);
+    test_option!(key_width, "--key-width=20", key_width, 20);
+    test_option_fail!(key_width_zero, "--key-width=0");
+    test_option!(truncate_middle, "--truncate=middle", truncation, Truncation::Middle);
+    test_option!(truncate_path, "--truncate=path", truncation, Truncation::Path);
+    test_option_fail!(invalid_truncate, "--truncate=start");
+    test_option!(min_bar, "--min-bar=0", min_bar, 0);
+
+    test_option!(no_window, "", offset, 0, bottom, 0, min_count, 0, max_count, 0);
+    test_option!(limit, "--limit=40", height, 40, height_arg, 40);
+    test_option!(offset, "--offset=20", offset, 20);
+    test_option!(bottom, "--bottom=5", bottom, 5);
+    test_option_fail!(bottom_zero, "--bottom=0");
+    test_option!(min_count, "--min-count=2", min_count, 2);
+    test_option!(max_count, "--max-count=1", max_count, 1);
+    test_option_fail!(max_count_zero, "--max-count=0");
+    test_option_fail!(invalid_offset, "--offset=-1");
+
+    test_option!(no_cumulative, "", cumulative, false, cutoff, 0.0);
+    test_option!(cumulative, "--cumulative", cumulative, true);
+    test_option!(cutoff, "--cutoff=80", cutoff, 80.0, cumulative, true);
+    test_option!(cutoff_percent, "--cutoff=92.5%", cutoff, 92.5);
+    test_option_fail!(cutoff_zero, "--cutoff=0");
+    test_option_fail!(cutoff_too_large, "--cutoff=101");
+
+    test_option!(no_other, "", other, false, totals, false);
+    test_option!(other, "--other", other, true);
+    test_option!(totals, "--totals", totals, true);
+
+    test_option!(no_compare, "", compare, Vec::<String>::new());
+    test_option!(compare, "--compare=yesterday.log", compare, vec![String::from("yesterday.log")]);
+    test_option!(
+        compare_files,
+        "--compare=yesterday.log,today.log",
+        compare,
+        vec![String::from("yesterday.log"), String::from("today.log")]
+    );
+    test_option_fail!(compare_too_many, "--compare=a,b,c");
+    test_option!(by_change, "--by-change", sort_order, SortOrder::Change);
+
+    test_option!(sort_count, "--sort=count", sort_order, SortOrder::Count);
+    test_option!(sort_count_asc, "--sort=count-asc", sort_order, SortOrder::CountAscending);
+    test_option!(sort_key, "--sort=key", sort_order, SortOrder::Key);
+    test_option!(sort_natural, "--sort=natural", sort_order, SortOrder::Natural);
+    test_option!(sort_input, "--sort=input", sort_order, SortOrder::Input, threads, 1);
+    test_option!(sort_change, "--sort=change", sort_order, SortOrder::Change);
+    test_option_fail!(invalid_sort, "--sort=random");
+
+    #[test]
+    fn sort_overrides_bins() {
+        let args = vec!["test", "--rcfile=/dev/null", "--sort=count", "--bins=10"];
+        let s = Settings::new(args.into_iter().map(String::from));
+
+        assert_eq!(s.sort_order, SortOrder::Count);
+    }
+
+    test_option!(output_openmetrics, "--output=openmetrics", output_format, OutputFormat::OpenMetrics);
+    test_option!(
+        no_metric_options,
+        "",
+        metric_name,
+        "distribution_keys",
+        metric_label,
+        "key",
+        metric_type,
+        MetricType::Gauge
+    );
+    test_option!(metric_name, "--metric-name=http:requests", metric_name, "http:requests");
+    test_option_fail!(invalid_metric_name, "--metric-name=2xx");
+    test_option!(metric_label, "--metric-label=status", metric_label, "status");
+    test_option_fail!(invalid_metric_label, "--metric-label=a:b");
+    test_option_fail!(reserved_metric_label, "--metric-label=__name__");
+    test_option!(metric_counter, "--metric-type=counter", metric_type, MetricType::Counter);
+    test_option_fail!(invalid_metric_type, "--metric-type=histogram");
+
+    test_option!(no_orientation, "", orientation, Orientation::Horizontal);
+    test_option!(
+        orientation_horizontal,
+        "--orientation=horizontal",
+        orientation,
+        Orientation::Horizontal
+    );
+    test_option!(orientation_vertical, "--orientation=vertical", orientation, Orientation::Vertical);
+    test_option_fail!(invalid_orientation, "--orientation=diagonal");
+
+    #[test]
+    fn vertical_partial_blocks() {
+        let args = vec!["test", "--rcfile=/dev/null", "--char=pb", "--orientation=vertical"];
+        let s = Settings::new(args.into_iter().map(String::from));
+        assert_eq!(s.graph_chars, vec!['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█']);
+    }
+
+    #[test]
+    #[should_panic]
+    fn vertical_stack() {
+        let args = vec!["test", "--rcfile=/dev/null", "--stack", "--orientation=vertical"];
+        Settings::new(args.into_iter().map(String::from));
+    }
+
+    test_option!(no_title, "", title, None);
+    test_option!(title, "--title=Status codes, a=b", title, Some(String::from("Status codes, a=b")));
+
+    test_option!(no_stack, "", stack, None);
+    test_option!(stack, "--stack", stack, Some(String::new()));
+    test_option!(stack_regex, r"--stack=(\w+) (\d+)", stack, Some(String::from(r"(\w+) (\d+)")));
+
+    test_option!(no_statistics, "", statistics, false);
+    test_option!(statistics, "--stats", statistics, true);
+
+    test_option!(default_threads, "", threads, 1);
+    test_option!(threads, "--threads=4", threads, 4);
+    test_option_fail!(invalid_threads, "--threads=many");
+
+    test_option!(no_bins, "", binning, Binning::NA, sort_order, SortOrder::Count);
+    test_option!(bins_count, "--bins=10", binning, Binning::Count(10), sort_order, SortOrder::Input);
+    test_option!(bins_log2, "--bins=log2", binning, Binning::Log(2));
+    test_option!(bins_log10, "--bins=log10", binning, Binning::Log(10));
+    test_option!(bins_sturges, "--bins=sturges", binning, Binning::Auto(BinRule::Sturges));
+    test_option!(bins_scott, "--bins=scott", binning, Binning::Auto(BinRule::Scott));
+    test_option!(bins_fd, "--bins=fd", binning, Binning::Auto(BinRule::FreedmanDiaconis));
+    test_option!(bins_auto, "--bins=auto", binning, Binning::Auto(BinRule::FreedmanDiaconis));
+    test_option!(bins_edges, "--bins=0,1.5,10", binning, Binning::Edges(vec![0.0, 1.5, 10.0]));
+    test_option!(
+        bin_width,
+        "--bin-width=0.5",
+        binning,
+        Binning::Width(0.5),
+        sort_order,
+        SortOrder::Input
+    );
+    test_option_fail!(invalid_bins, "--bins=many");
+    test_option_fail!(decreasing_bin_edges, "--bins=10,1");
+    test_option_fail!(invalid_bin_width, "--bin-width=0");
+
+    test_option!(default_approx, "", approx_keys, 0);
+    test_option!(approx, "--approx=1000", approx_keys, 1000);
+    test_option_fail!(invalid_approx, "--approx=lots");
 }
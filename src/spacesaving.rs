@@ -0,0 +1,203 @@
+use std::collections::BTreeSet;
+
+use super::hyperloglog::HyperLogLog;
+use super::pairlist::Pair;
+use super::summary::Summary;
+use super::tokenizer::Tally;
+
+use rustc_hash::FxHashMap;
+
+/// Approximate tally of the most frequent keys using the Space-Saving algorithm (Metwally et al.).
+///
+/// At most `capacity` keys are kept. When a new key arrives and the tally is full, it replaces the
+/// key with the smallest count and inherits that count as its possible error. Every count is an
+/// overestimate of the true count by at most its error, which is bounded by `total / capacity`,
+/// and any key occurring more than `total / capacity` times is guaranteed to be kept.
+///
+/// Since evicted keys are forgotten, the number of distinct keys is estimated separately.
+#[derive(Clone, Debug)]
+pub struct SpaceSaving {
+    capacity: usize,
+    counters: Vec<Counter>,
+    index: FxHashMap<String, usize>,
+    // (count, counter index), ordered so the smallest counter is first
+    by_count: BTreeSet<(u64, usize)>,
+    examined: u64,
+    evicted: bool,
+    distinct: HyperLogLog,
+}
+
+#[derive(Clone, Debug)]
+struct Counter {
+    key: String,
+    count: u64,
+    error: u64,
+}
+
+impl SpaceSaving {
+    pub fn new(capacity: usize) -> SpaceSaving {
+        assert!(capacity > 0, "SpaceSaving needs room for at least one key");
+        SpaceSaving {
+            capacity,
+            counters: Vec::with_capacity(capacity),
+            index: FxHashMap::default(),
+            by_count: BTreeSet::new(),
+            examined: 0,
+            evicted: false,
+            distinct: HyperLogLog::new(),
+        }
+    }
+
+    /// Adds `count` occurrences of `key`, which may already be overstated by up to `error`.
+    pub fn add(&mut self, key: &str, count: u64, error: u64) {
+        if let Some(&i) = self.index.get(key) {
+            let c = &mut self.counters[i];
+            self.by_count.remove(&(c.count, i));
+            c.count += count;
+            c.error += error;
+            self.by_count.insert((c.count, i));
+        } else if self.counters.len() < self.capacity {
+            let i = self.counters.len();
+            self.counters.push(Counter {
+                key: String::from(key),
+                count,
+                error,
+            });
+            self.index.insert(String::from(key), i);
+            self.by_count.insert((count, i));
+        } else {
+            let (min, i) = self.by_count.pop_first().expect("SpaceSaving is empty");
+            self.evicted = true;
+            let c = &mut self.counters[i];
+            self.index.remove(&c.key);
+            c.key.clear();
+            c.key.push_str(key);
+            c.count = min + count;
+            c.error = min + error;
+            self.index.insert(String::from(key), i);
+            self.by_count.insert((c.count, i));
+        }
+    }
+}
+
+impl Tally for SpaceSaving {
+    fn examine(&mut self) {
+        self.examined += 1
+    }
+
+    fn increment(&mut self, key: &str) {
+        self.distinct.insert(key);
+        self.add(key, 1, 0)
+    }
+
+    // replaying the other tally's counters as weighted updates keeps every count an overestimate,
+    // with the errors of both tallies added together
+    fn merge(&mut self, other: SpaceSaving) {
+        self.examined += other.examined;
+        self.evicted |= other.evicted;
+        self.distinct.merge(&other.distinct);
+        for c in other.counters {
+            self.add(&c.key, c.count, c.error);
+        }
+    }
+
+    // counts always add up to the number of keys counted, however many were evicted
+    fn summary(&self) -> Summary {
+        let matched = self.counters.iter().map(|c| c.count).sum();
+        let summary = Summary::new(self.examined, matched, self.counters.len() as u64);
+        if self.evicted {
+            summary.with_distinct_estimate(self.distinct.estimate())
+        } else {
+            summary
+        }
+    }
+
+    fn into_pairs(self) -> Vec<Pair> {
+        self.counters.iter().map(|c| Pair::approximate(c.count, c.error, &c.key)).collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::SpaceSaving;
+    use crate::pairlist::Pair;
+    use crate::summary::Summary;
+    use crate::tokenizer::Tally;
+
+    fn tally(capacity: usize, keys: &[&str]) -> Vec<Pair> {
+        let mut s = SpaceSaving::new(capacity);
+        for k in keys {
+            s.increment(k);
+        }
+        let mut pairs = s.into_pairs();
+        pairs.sort_by(|a, b| b.cmp(a));
+        pairs
+    }
+
+    #[test]
+    fn exact_within_capacity() {
+        let actual = tally(3, &["a", "b", "a", "c", "a", "b"]);
+        assert_eq!(actual, vec![Pair::new(3, "a"), Pair::new(2, "b"), Pair::new(1, "c")]);
+    }
+
+    #[test]
+    fn evicts_smallest_counter() {
+        let actual = tally(2, &["a", "a", "a", "b", "c"]);
+        assert_eq!(actual, vec![Pair::new(3, "a"), Pair::approximate(2, 1, "c")]);
+    }
+
+    #[test]
+    fn counts_sum_to_total() {
+        let keys: Vec<String> = (0..1000).map(|i| format!("{}", (i * i) % 37)).collect();
+        let keys: Vec<&str> = keys.iter().map(String::as_str).collect();
+        let actual = tally(8, &keys);
+
+        assert_eq!(actual.len(), 8);
+        assert_eq!(actual.iter().map(Pair::value).sum::<u64>(), 1000);
+    }
+
+    #[test]
+    fn keeps_heavy_hitters() {
+        let mut keys = Vec::new();
+        for i in 0..300 {
+            keys.push("hot");
+            keys.push(["x", "y", "z", "w", "v"][i % 5]);
+        }
+        let actual = tally(3, &keys);
+
+        assert_eq!(actual[0].key(), "hot");
+        assert!(actual[0].value() >= 300);
+        assert!(actual[0].value() - actual[0].error() <= 300);
+    }
+
+    #[test]
+    fn merge() {
+        let mut a = SpaceSaving::new(2);
+        let mut b = SpaceSaving::new(2);
+        for k in ["a", "a", "b"] {
+            a.increment(k);
+        }
+        for k in ["a", "c", "c"] {
+            b.increment(k);
+        }
+        a.merge(b);
+
+        let mut actual = a.into_pairs();
+        actual.sort_by(|a, b| b.cmp(a));
+        assert_eq!(actual, vec![Pair::approximate(3, 1, "c"), Pair::new(3, "a")]);
+    }
+
+    #[test]
+    fn summary_exact_until_evicted() {
+        let mut s = SpaceSaving::new(2);
+        for k in ["a", "b", "a"] {
+            s.examine();
+            s.increment(k);
+        }
+        assert_eq!(s.summary(), Summary::new(3, 3, 2));
+
+        s.examine();
+        s.increment("c");
+        assert_eq!(s.summary(), Summary::new(4, 4, 2).with_distinct_estimate(3));
+    }
+}
@@ -0,0 +1,163 @@
+use std::io;
+
+use regex::Regex;
+use rustc_hash::FxHashMap;
+
+use super::pairlist::Pair;
+use super::summary::Summary;
+use super::tokenizer::{for_each_line, FxIndexMap};
+
+// secondary keys beyond this many are drawn together as "(other)"
+pub const MAX_CATEGORIES: usize = 8;
+
+/// Splits each line into a primary and a secondary key using the first two capture groups of a
+/// regex, counting the primary keys for the histogram and the secondary keys within each of them.
+pub struct StackTokenizer {
+    re: Regex,
+}
+
+/// How the count of each primary key breaks down by secondary key.
+#[derive(Debug, Default)]
+pub struct Breakdown {
+    // most frequent first, with any beyond MAX_CATEGORIES lumped together at the end
+    categories: Vec<String>,
+    counts: FxHashMap<String, Vec<u64>>,
+}
+
+impl StackTokenizer {
+    /// An empty `matcher` splits each line into its first two whitespace-separated fields.
+    pub fn new(matcher: &str) -> StackTokenizer {
+        let matcher = if matcher.is_empty() { r"^\s*(\S+)\s+(\S+)" } else { matcher };
+        let re = Regex::new(matcher).unwrap();
+        assert!(re.captures_len() > 2, "--stack needs a regex with two capture groups");
+        StackTokenizer { re }
+    }
+
+    /// Returns the primary keys, in order of first appearance, and their counts along with the
+    /// breakdown of each. Lines that do not match are skipped.
+    pub fn count<T: io::BufRead>(&self, reader: T) -> (Vec<Pair>, Breakdown, Summary) {
+        let mut counts: FxIndexMap<String, FxHashMap<String, u64>> = FxIndexMap::default();
+        let mut examined = 0;
+        let mut matched = 0;
+        for_each_line(reader, |line| {
+            examined += 1;
+            if let Some(c) = self.re.captures(line) {
+                if let (Some(primary), Some(secondary)) = (c.get(1), c.get(2)) {
+                    matched += 1;
+                    let row = counts.entry(primary.as_str().to_string()).or_default();
+                    *row.entry(secondary.as_str().to_string()).or_insert(0) += 1;
+                }
+            }
+        });
+
+        let pairs: Vec<Pair> = counts.iter().map(|(k, row)| Pair::new(row.values().sum(), k)).collect();
+        let summary = Summary::new(examined, matched, pairs.len() as u64);
+        (pairs, Breakdown::new(counts), summary)
+    }
+}
+
+impl Breakdown {
+    fn new(counts: FxIndexMap<String, FxHashMap<String, u64>>) -> Breakdown {
+        let mut totals: FxHashMap<&str, u64> = FxHashMap::default();
+        for row in counts.values() {
+            for (k, v) in row {
+                *totals.entry(k).or_insert(0) += v;
+            }
+        }
+        let mut totals: Vec<(&str, u64)> = totals.into_iter().collect();
+        totals.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+
+        let mut categories: Vec<String> = totals.iter().take(MAX_CATEGORIES).map(|(k, _)| k.to_string()).collect();
+        if totals.len() > MAX_CATEGORIES {
+            // the last slot holds everything else
+            categories.truncate(MAX_CATEGORIES - 1);
+            categories.push(String::from("(other)"));
+        }
+        let index: FxHashMap<String, usize> = totals
+            .iter()
+            .enumerate()
+            .map(|(i, (k, _))| (k.to_string(), i.min(categories.len() - 1)))
+            .collect();
+
+        let counts = counts
+            .into_iter()
+            .map(|(primary, row)| {
+                let mut segments = vec![0; categories.len()];
+                for (k, v) in row {
+                    segments[index[&k]] += v;
+                }
+                (primary, segments)
+            })
+            .collect();
+
+        Breakdown { categories, counts }
+    }
+
+    /// Secondary keys in the order their segments are drawn.
+    pub fn categories(&self) -> &[String] {
+        &self.categories
+    }
+
+    /// The count of each category within `primary`, in the order of `categories`.
+    pub fn segments(&self, primary: &str) -> &[u64] {
+        self.counts.get(primary).map(Vec::as_slice).unwrap_or(&[])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{StackTokenizer, MAX_CATEGORIES};
+
+    #[test]
+    fn whitespace_fields() {
+        let input = "GET 200\nGET 404\nPOST 200\nGET 200\nmalformed\n";
+        let (mut pairs, breakdown, summary) = StackTokenizer::new("").count(input.as_bytes());
+        pairs.sort_by(|a, b| b.cmp(a));
+
+        assert_eq!(
+            pairs.iter().map(|p| (p.key(), p.value())).collect::<Vec<_>>(),
+            [("GET", 3), ("POST", 1)]
+        );
+        assert_eq!(breakdown.categories(), ["200", "404"]);
+        assert_eq!(breakdown.segments("GET"), [2, 1]);
+        assert_eq!(breakdown.segments("POST"), [1, 0]);
+        assert_eq!(breakdown.segments("PUT"), [] as [u64; 0]);
+        assert_eq!((summary.examined(), summary.matched(), summary.keys()), (5, 4, 2));
+    }
+
+    #[test]
+    fn first_appearance_order() {
+        let input = "zeta 1\nalpha 1\nmid 1\nbeta 1\nalpha 2\n";
+        let (pairs, _, _) = StackTokenizer::new("").count(input.as_bytes());
+        assert_eq!(pairs.iter().map(|p| p.key()).collect::<Vec<_>>(), ["zeta", "alpha", "mid", "beta"]);
+    }
+
+    #[test]
+    fn capture_groups() {
+        let input = "Jan  1 cron[12]: info started\nJan  1 sshd[3]: warn refused\nJan  1 cron[13]: info done\n";
+        let (pairs, breakdown, _) = StackTokenizer::new(r"(\w+)\[\d+\]: (\w+)").count(input.as_bytes());
+
+        assert_eq!(pairs.len(), 2);
+        assert_eq!(breakdown.categories(), ["info", "warn"]);
+        assert_eq!(breakdown.segments("cron"), [2, 0]);
+        assert_eq!(breakdown.segments("sshd"), [0, 1]);
+    }
+
+    #[test]
+    fn other_categories() {
+        let input: String = (0..MAX_CATEGORIES + 2).map(|i| format!("a {}\n", i).repeat(20 - i)).collect();
+        let (_, breakdown, _) = StackTokenizer::new("").count(input.as_bytes());
+
+        assert_eq!(breakdown.categories().len(), MAX_CATEGORIES);
+        assert_eq!(breakdown.categories().last().unwrap(), "(other)");
+        assert_eq!(breakdown.categories()[0], "0");
+        // 20 - 7 + 20 - 8 + 20 - 9
+        assert_eq!(breakdown.segments("a")[MAX_CATEGORIES - 1], 36);
+    }
+
+    #[test]
+    #[should_panic]
+    fn one_capture_group() {
+        StackTokenizer::new(r"(\w+) \w+");
+    }
+}
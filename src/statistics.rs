@@ -0,0 +1,259 @@
+use std::cell::OnceCell;
+use std::collections::BTreeMap;
+
+use super::numeric::quantile;
+
+// values kept exactly before switching to the quantile sketch
+const EXACT_LIMIT: usize = 100_000;
+
+// relative accuracy of quantiles once the sketch is in use
+const SKETCH_ACCURACY: f64 = 0.01;
+
+/// Summary statistics of a stream of numbers, reported by `--stats`.
+///
+/// Count, min, max, mean and standard deviation are always exact. Quantiles are exact until
+/// `EXACT_LIMIT` values have been seen, after which the values are moved into a sketch and
+/// quantiles are accurate to within 1% of their true value.
+#[derive(Debug)]
+pub struct Statistics {
+    count: u64,
+    min: f64,
+    max: f64,
+    mean: f64,
+    // sum of squared differences from the mean (Welford's algorithm)
+    m2: f64,
+    values: Vec<f64>,
+    // values sorted on the first quantile asked for since the last insert
+    sorted: OnceCell<Vec<f64>>,
+    sketch: Option<QuantileSketch>,
+}
+
+impl Default for Statistics {
+    fn default() -> Statistics {
+        Statistics::new()
+    }
+}
+
+impl Statistics {
+    pub fn new() -> Statistics {
+        Statistics {
+            count: 0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+            mean: 0.0,
+            m2: 0.0,
+            values: Vec::new(),
+            sorted: OnceCell::new(),
+            sketch: None,
+        }
+    }
+
+    pub fn insert(&mut self, value: f64) {
+        self.count += 1;
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+        let delta = value - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (value - self.mean);
+        self.sorted.take();
+
+        match self.sketch {
+            Some(ref mut sketch) => sketch.insert(value),
+            None if self.values.len() < EXACT_LIMIT => self.values.push(value),
+            None => {
+                let mut sketch = QuantileSketch::new(SKETCH_ACCURACY);
+                for v in self.values.drain(..) {
+                    sketch.insert(v);
+                }
+                sketch.insert(value);
+                self.values.shrink_to_fit();
+                self.sketch = Some(sketch);
+            }
+        }
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn min(&self) -> f64 {
+        self.min
+    }
+
+    pub fn max(&self) -> f64 {
+        self.max
+    }
+
+    pub fn mean(&self) -> f64 {
+        self.mean
+    }
+
+    /// Population standard deviation.
+    pub fn stddev(&self) -> f64 {
+        if self.count == 0 {
+            0.0
+        } else {
+            (self.m2 / self.count as f64).sqrt()
+        }
+    }
+
+    /// Whether quantiles are exact rather than estimated by the sketch.
+    pub fn exact(&self) -> bool {
+        self.sketch.is_none()
+    }
+
+    /// The `q`th quantile, for `q` between 0 and 1.
+    pub fn quantile(&self, q: f64) -> f64 {
+        match self.sketch {
+            // the sketch's buckets are only accurate to 1%, so keep the extremes exact
+            Some(ref sketch) => sketch.quantile(q).clamp(self.min, self.max),
+            None if self.values.is_empty() => f64::NAN,
+            None => {
+                let sorted = self.sorted.get_or_init(|| {
+                    let mut sorted = self.values.clone();
+                    sorted.sort_by(f64::total_cmp);
+                    sorted
+                });
+                quantile(sorted, q)
+            }
+        }
+    }
+}
+
+/// Streaming quantile estimates with bounded relative error (Masson et al., DDSketch).
+///
+/// Values are counted in buckets whose boundaries grow geometrically, so every value in a bucket
+/// is within the requested relative accuracy of the bucket's midpoint.
+#[derive(Debug)]
+struct QuantileSketch {
+    gamma: f64,
+    positive: BTreeMap<i32, u64>,
+    negative: BTreeMap<i32, u64>,
+    zeros: u64,
+    count: u64,
+}
+
+impl QuantileSketch {
+    fn new(accuracy: f64) -> QuantileSketch {
+        QuantileSketch {
+            gamma: (1.0 + accuracy) / (1.0 - accuracy),
+            positive: BTreeMap::new(),
+            negative: BTreeMap::new(),
+            zeros: 0,
+            count: 0,
+        }
+    }
+
+    fn bucket(&self, magnitude: f64) -> i32 {
+        magnitude.log(self.gamma).ceil() as i32
+    }
+
+    // midpoint of the bucket (gamma^(k-1), gamma^k], in relative terms
+    fn value(&self, bucket: i32) -> f64 {
+        2.0 * self.gamma.powi(bucket) / (self.gamma + 1.0)
+    }
+
+    fn insert(&mut self, value: f64) {
+        self.count += 1;
+        if value > 0.0 {
+            *self.positive.entry(self.bucket(value)).or_default() += 1;
+        } else if value < 0.0 {
+            *self.negative.entry(self.bucket(-value)).or_default() += 1;
+        } else {
+            self.zeros += 1;
+        }
+    }
+
+    fn quantile(&self, q: f64) -> f64 {
+        let rank = (q * (self.count - 1) as f64).round() as u64;
+        let mut seen = 0;
+        // most negative values first
+        for (&bucket, &n) in self.negative.iter().rev() {
+            seen += n;
+            if seen > rank {
+                return -self.value(bucket);
+            }
+        }
+        seen += self.zeros;
+        if seen > rank {
+            return 0.0;
+        }
+        for (&bucket, &n) in &self.positive {
+            seen += n;
+            if seen > rank {
+                return self.value(bucket);
+            }
+        }
+        f64::NAN
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{QuantileSketch, Statistics, EXACT_LIMIT};
+
+    fn statistics(values: &[f64]) -> Statistics {
+        let mut s = Statistics::new();
+        for &v in values {
+            s.insert(v);
+        }
+        s
+    }
+
+    #[test]
+    fn empty() {
+        let s = Statistics::new();
+        assert_eq!(s.count(), 0);
+        assert_eq!(s.stddev(), 0.0);
+        assert!(s.quantile(0.5).is_nan());
+    }
+
+    #[test]
+    fn exact_statistics() {
+        let s = statistics(&[2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0]);
+        assert!(s.exact());
+        assert_eq!(s.count(), 8);
+        assert_eq!(s.min(), 2.0);
+        assert_eq!(s.max(), 9.0);
+        assert_eq!(s.mean(), 5.0);
+        assert_eq!(s.stddev(), 2.0);
+        assert_eq!(s.quantile(0.5), 4.5);
+        assert_eq!(s.quantile(1.0), 9.0);
+    }
+
+    #[test]
+    fn insert_after_quantile() {
+        let mut s = statistics(&[1.0, 2.0, 3.0]);
+        assert_eq!(s.quantile(0.5), 2.0);
+        s.insert(10.0);
+        s.insert(11.0);
+        assert_eq!(s.quantile(0.5), 3.0);
+    }
+
+    #[test]
+    fn sketched_statistics() {
+        let n = 3 * EXACT_LIMIT;
+        let values: Vec<f64> = (0..n).map(|i| ((i * 7919) % n) as f64 + 1.0).collect();
+        let s = statistics(&values);
+
+        assert!(!s.exact());
+        assert_eq!(s.count(), n as u64);
+        assert_eq!(s.min(), 1.0);
+        assert_eq!(s.max(), n as f64);
+        for q in [0.5, 0.9, 0.99, 0.999] {
+            let expected = q * n as f64;
+            assert!((s.quantile(q) - expected).abs() / expected < 0.011, "q={}", q);
+        }
+    }
+
+    #[test]
+    fn sketch_negative_and_zero() {
+        let mut sketch = QuantileSketch::new(0.01);
+        for v in [-100.0, -1.0, 0.0, 0.0, 1.0, 100.0] {
+            sketch.insert(v);
+        }
+        assert!((sketch.quantile(0.0) + 100.0).abs() < 1.0);
+        assert_eq!(sketch.quantile(0.5), 0.0);
+        assert!((sketch.quantile(1.0) - 100.0).abs() < 1.0);
+    }
+}
@@ -0,0 +1,65 @@
+use std::time::Duration;
+
+/// Statistics about the input gathered while counting it, reported by `--verbose`.
+#[derive(Debug, Default, PartialEq)]
+pub struct Summary {
+    examined: u64,
+    matched: u64,
+    keys: u64,
+    distinct: u64,
+    distinct_estimated: bool,
+    runtime: Duration,
+}
+
+impl Summary {
+    /// A summary where every distinct key was kept, so `keys` is also the distinct count.
+    pub fn new(examined: u64, matched: u64, keys: u64) -> Summary {
+        Summary {
+            examined,
+            matched,
+            keys,
+            distinct: keys,
+            ..Default::default()
+        }
+    }
+
+    /// Replaces the distinct count with an estimate, for when not every key was kept.
+    pub fn with_distinct_estimate(self, distinct: u64) -> Summary {
+        Summary {
+            distinct,
+            distinct_estimated: true,
+            ..self
+        }
+    }
+
+    pub fn with_runtime(self, runtime: Duration) -> Summary {
+        Summary { runtime, ..self }
+    }
+
+    /// Tokens (or lines) read from the input.
+    pub fn examined(&self) -> u64 {
+        self.examined
+    }
+
+    /// Tokens (or lines) that matched and were counted.
+    pub fn matched(&self) -> u64 {
+        self.matched
+    }
+
+    /// Keys kept for the histogram.
+    pub fn keys(&self) -> u64 {
+        self.keys
+    }
+
+    pub fn distinct(&self) -> u64 {
+        self.distinct
+    }
+
+    pub fn distinct_estimated(&self) -> bool {
+        self.distinct_estimated
+    }
+
+    pub fn runtime(&self) -> Duration {
+        self.runtime
+    }
+}
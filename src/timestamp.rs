@@ -0,0 +1,454 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::pairlist::Pair;
+use super::settings::TimeFormat;
+use super::tokenizer::{for_each_line, Tokenizer};
+
+// most intervals filled in between the first and last timestamp; beyond this, as when a single
+// outlier is far from the rest, only intervals with timestamps are returned
+const MAX_FILLED_INTERVALS: i64 = 100_000;
+
+const MONTHS: [&str; 12] = ["jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec"];
+
+/// Reads a timestamp from the start of each line and counts how many fall into each interval.
+///
+/// Intervals are aligned to the Unix epoch in UTC and returned in chronological order, with empty
+/// intervals between the first and last included unless there would be too many of them. Lines
+/// without a timestamp are skipped.
+pub struct TimeTokenizer<'a> {
+    format: &'a TimeFormat,
+    interval: i64,
+    // syslog timestamps have no year
+    default_year: i64,
+}
+
+impl<'a> TimeTokenizer<'a> {
+    /// `interval` is the width of each bucket in seconds.
+    pub fn new(format: &'a TimeFormat, interval: u64) -> TimeTokenizer<'a> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        let (default_year, _, _) = civil_from_days(now as i64 / 86400);
+        TimeTokenizer {
+            format,
+            interval: interval.max(1) as i64,
+            default_year,
+        }
+    }
+
+    /// Returns the number of timestamps in each interval, keyed by its start, along with the
+    /// number of lines examined.
+    pub fn read_buckets<T: io::BufRead>(&self, reader: T) -> (BTreeMap<i64, u64>, u64) {
+        let mut buckets = BTreeMap::new();
+        let mut examined = 0;
+        for_each_line(reader, |line| {
+            examined += 1;
+            // the start of the interval, unless it is before the earliest time an i64 holds
+            if let Some(start) = self.parse(line).and_then(|t| t.checked_sub(t.rem_euclid(self.interval))) {
+                *buckets.entry(start).or_insert(0) += 1;
+            }
+        });
+
+        (buckets, examined)
+    }
+
+    /// Returns a pair per interval, along with whether the empty intervals were left out.
+    pub fn pairs(&self, buckets: &BTreeMap<i64, u64>) -> (Vec<Pair>, bool) {
+        let (first, last) = match (buckets.keys().next(), buckets.keys().next_back()) {
+            (Some(&first), Some(&last)) => (first, last),
+            _ => return (Vec::new(), false),
+        };
+
+        // a span too wide for an i64 is certainly too many intervals
+        let intervals = last.checked_sub(first).map_or(i64::MAX, |span| span / self.interval);
+        if intervals >= MAX_FILLED_INTERVALS {
+            let pairs = buckets.iter().map(|(&t, &count)| Pair::new(count, &self.label(t))).collect();
+            return (pairs, true);
+        }
+
+        let mut pairs = Vec::new();
+        let mut t = Some(first);
+        while let Some(start) = t.filter(|&start| start <= last) {
+            pairs.push(Pair::new(*buckets.get(&start).unwrap_or(&0), &self.label(start)));
+            t = start.checked_add(self.interval);
+        }
+        (pairs, false)
+    }
+
+    // only as precise as the interval needs
+    fn label(&self, t: i64) -> String {
+        let (y, m, d) = civil_from_days(t.div_euclid(86400));
+        let secs = t.rem_euclid(86400);
+        let (hh, mm, ss) = (secs / 3600, secs % 3600 / 60, secs % 60);
+        if self.interval % 86400 == 0 {
+            format!("{:04}-{:02}-{:02}", y, m, d)
+        } else if self.interval % 60 == 0 {
+            format!("{:04}-{:02}-{:02} {:02}:{:02}", y, m, d, hh, mm)
+        } else {
+            format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02}", y, m, d, hh, mm, ss)
+        }
+    }
+
+    /// Parses the timestamp at the start of `line` as seconds since the Unix epoch.
+    pub fn parse(&self, line: &str) -> Option<i64> {
+        let line = line.trim_start();
+        match self.format {
+            TimeFormat::NA => None,
+            TimeFormat::Iso8601 => parse_iso8601(line),
+            TimeFormat::Syslog => Parser::new(line, self.default_year).parse("%b %e %H:%M:%S"),
+            TimeFormat::Epoch => parse_epoch(line, 1.0),
+            TimeFormat::EpochMillis => parse_epoch(line, 1000.0),
+            TimeFormat::Strftime(f) => Parser::new(line, self.default_year).parse(f),
+        }
+    }
+}
+
+impl<'a> Tokenizer for TimeTokenizer<'a> {
+    fn tokenize<T: io::BufRead>(&self, reader: T) -> Vec<Pair> {
+        let (buckets, _) = self.read_buckets(reader);
+        self.pairs(&buckets).0
+    }
+}
+
+fn parse_epoch(line: &str, per_second: f64) -> Option<i64> {
+    let end = line.find(|c: char| c.is_whitespace()).unwrap_or(line.len());
+    let t = line[..end].parse::<f64>().ok()?;
+    if t.is_finite() {
+        Some((t / per_second).floor() as i64)
+    } else {
+        None
+    }
+}
+
+// a date, optionally followed by a time, fractional seconds and a UTC offset
+fn parse_iso8601(line: &str) -> Option<i64> {
+    let mut p = Parser::new(line, 1970);
+    p.directive('Y')?;
+    p.literal('-')?;
+    p.directive('m')?;
+    p.literal('-')?;
+    p.directive('d')?;
+
+    let date_only = p.clone();
+    if p.literal('T').is_none() && p.whitespace().is_none() {
+        return date_only.finish();
+    }
+    if p.directive('H')
+        .and_then(|_| p.literal(':'))
+        .and_then(|_| p.directive('M'))
+        .is_none()
+    {
+        return date_only.finish();
+    }
+    if p.literal(':').is_some() {
+        p.directive('S')?;
+        p.directive('f');
+    }
+    if p.literal('Z').is_none() {
+        p.directive('z');
+    }
+    p.finish()
+}
+
+/// Parses a prefix of a line against a strftime-style format.
+///
+/// Supports `%Y %y %m %d %e %j %H %M %S %f %b %z %s %T %F %%`; whitespace in the format matches
+/// any run of whitespace, and any other character matches itself.
+#[derive(Clone)]
+struct Parser<'a> {
+    input: &'a str,
+    year: i64,
+    month: i64,
+    day: i64,
+    day_of_year: Option<i64>,
+    hour: i64,
+    minute: i64,
+    second: i64,
+    offset: i64,
+    epoch: Option<i64>,
+}
+
+impl<'a> Parser<'a> {
+    fn new(input: &'a str, year: i64) -> Parser<'a> {
+        Parser {
+            input,
+            year,
+            month: 1,
+            day: 1,
+            day_of_year: None,
+            hour: 0,
+            minute: 0,
+            second: 0,
+            offset: 0,
+            epoch: None,
+        }
+    }
+
+    fn parse(mut self, format: &str) -> Option<i64> {
+        let mut chars = format.chars();
+        while let Some(c) = chars.next() {
+            if c == '%' {
+                match chars.next()? {
+                    'T' => self.parse_nested("%H:%M:%S")?,
+                    'F' => self.parse_nested("%Y-%m-%d")?,
+                    '%' => self.literal('%')?,
+                    d => self.directive(d)?,
+                }
+            } else if c.is_whitespace() {
+                self.whitespace()?;
+            } else {
+                self.literal(c)?;
+            }
+        }
+        self.finish()
+    }
+
+    fn parse_nested(&mut self, format: &str) -> Option<()> {
+        let mut chars = format.chars();
+        while let Some(c) = chars.next() {
+            if c == '%' {
+                self.directive(chars.next()?)?;
+            } else {
+                self.literal(c)?;
+            }
+        }
+        Some(())
+    }
+
+    fn finish(&self) -> Option<i64> {
+        if let Some(epoch) = self.epoch {
+            return Some(epoch);
+        }
+        let days = match self.day_of_year {
+            Some(yday) => days_from_civil(self.year, 1, 1) + yday - 1,
+            None => {
+                if !(1..=12).contains(&self.month) || !(1..=31).contains(&self.day) {
+                    return None;
+                }
+                days_from_civil(self.year, self.month, self.day)
+            }
+        };
+        if self.hour > 23 || self.minute > 59 || self.second > 60 {
+            return None;
+        }
+        Some(days * 86400 + self.hour * 3600 + self.minute * 60 + self.second - self.offset)
+    }
+
+    fn directive(&mut self, d: char) -> Option<()> {
+        match d {
+            'Y' => self.year = self.number(4, 4)?,
+            'y' => self.year = 2000 + self.number(2, 2)?,
+            'm' => self.month = self.number(1, 2)?,
+            'd' => self.day = self.number(1, 2)?,
+            'e' => {
+                self.whitespace();
+                self.day = self.number(1, 2)?
+            }
+            'j' => self.day_of_year = Some(self.number(1, 3)?),
+            'H' => self.hour = self.number(1, 2)?,
+            'M' => self.minute = self.number(1, 2)?,
+            'S' => self.second = self.number(1, 2)?,
+            // fractions of a second are dropped
+            'f' => {
+                self.literal('.').or_else(|| self.literal(','))?;
+                self.number(1, 9)?;
+            }
+            'b' => self.month = self.month_name()?,
+            'z' => self.offset = self.offset()?,
+            's' => {
+                let negative = self.literal('-').is_some();
+                let t = self.number(1, 19)?;
+                self.epoch = Some(if negative { -t } else { t });
+            }
+            _ => return None,
+        }
+        Some(())
+    }
+
+    fn number(&mut self, min: usize, max: usize) -> Option<i64> {
+        let len = self.input.bytes().take(max).take_while(u8::is_ascii_digit).count();
+        if len < min {
+            return None;
+        }
+        let (digits, rest) = self.input.split_at(len);
+        self.input = rest;
+        digits.parse().ok()
+    }
+
+    fn literal(&mut self, c: char) -> Option<()> {
+        self.input = self.input.strip_prefix(c)?;
+        Some(())
+    }
+
+    fn whitespace(&mut self) -> Option<()> {
+        let rest = self.input.trim_start();
+        if rest.len() == self.input.len() {
+            return None;
+        }
+        self.input = rest;
+        Some(())
+    }
+
+    fn month_name(&mut self) -> Option<i64> {
+        let name = self.input.get(..3)?.to_ascii_lowercase();
+        let month = MONTHS.iter().position(|&m| m == name)? as i64 + 1;
+        self.input = &self.input[3..];
+        // accept full month names too
+        self.input = self.input.trim_start_matches(|c: char| c.is_ascii_alphabetic());
+        Some(month)
+    }
+
+    // +hhmm, +hh:mm or +hh, in seconds east of UTC
+    fn offset(&mut self) -> Option<i64> {
+        let sign = if self.literal('+').is_some() {
+            1
+        } else if self.literal('-').is_some() {
+            -1
+        } else {
+            return None;
+        };
+        let hours = self.number(2, 2)?;
+        self.literal(':');
+        let minutes = self.number(2, 2).unwrap_or(0);
+        Some(sign * (hours * 3600 + minutes * 60))
+    }
+}
+
+// days since 1970-01-01 of a proleptic Gregorian date (Howard Hinnant's algorithm)
+fn days_from_civil(y: i64, m: i64, d: i64) -> i64 {
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let doy = (153 * (m + if m > 2 { -3 } else { 9 }) + 2) / 5 + d - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+fn civil_from_days(z: i64) -> (i64, i64, i64) {
+    let z = z + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    (if m <= 2 { yoe + era * 400 + 1 } else { yoe + era * 400 }, m, d)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{civil_from_days, days_from_civil, TimeTokenizer};
+    use crate::pairlist::Pair;
+    use crate::settings::TimeFormat;
+    use crate::tokenizer::Tokenizer;
+    use std::io;
+
+    fn parse(format: TimeFormat, line: &str) -> Option<i64> {
+        TimeTokenizer::new(&format, 1).parse(line)
+    }
+
+    #[test]
+    fn civil_days() {
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(days_from_civil(2000, 3, 1), 11017);
+        assert_eq!(days_from_civil(1969, 12, 31), -1);
+        assert_eq!(civil_from_days(11017), (2000, 3, 1));
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+    }
+
+    #[test]
+    fn iso8601() {
+        assert_eq!(parse(TimeFormat::Iso8601, "2012-01-01  08:01:52  anacron"), Some(1325404912));
+        assert_eq!(parse(TimeFormat::Iso8601, "2012-01-01T08:01:52.250Z"), Some(1325404912));
+        assert_eq!(parse(TimeFormat::Iso8601, "2012-01-01T09:01:52+01:00"), Some(1325404912));
+        assert_eq!(parse(TimeFormat::Iso8601, "2012-01-01T08:01 x"), Some(1325404860));
+        assert_eq!(parse(TimeFormat::Iso8601, "2012-01-01 anacron"), Some(1325376000));
+        assert_eq!(parse(TimeFormat::Iso8601, "2012-13-01"), None);
+        assert_eq!(parse(TimeFormat::Iso8601, "anacron"), None);
+    }
+
+    #[test]
+    fn epoch() {
+        assert_eq!(parse(TimeFormat::Epoch, "1325404912 GET /"), Some(1325404912));
+        assert_eq!(parse(TimeFormat::Epoch, "1325404912.9"), Some(1325404912));
+        assert_eq!(parse(TimeFormat::EpochMillis, "1325404912250"), Some(1325404912));
+        assert_eq!(parse(TimeFormat::Epoch, "GET /"), None);
+    }
+
+    #[test]
+    fn syslog() {
+        let t = TimeTokenizer::new(&TimeFormat::Syslog, 1);
+        let year = t.default_year;
+        let expected = (days_from_civil(year, 1, 1) * 86400) + 8 * 3600 + 60 + 52;
+        assert_eq!(t.parse("Jan  1 08:01:52 host anacron[14182]"), Some(expected));
+        assert_eq!(t.parse("Foo  1 08:01:52"), None);
+    }
+
+    #[test]
+    fn strftime() {
+        let f = |s: &str| TimeFormat::Strftime(String::from(s));
+        assert_eq!(
+            parse(f("%d/%b/%Y:%T %z"), "[01/Jan/2012:09:01:52 +0100]".trim_start_matches('[')),
+            Some(1325404912)
+        );
+        assert_eq!(parse(f("%F %H"), "2012-01-01 08"), Some(1325404800));
+        assert_eq!(parse(f("%y%j"), "12002"), Some(1325462400));
+        assert_eq!(parse(f("%s"), "1325404912"), Some(1325404912));
+        assert_eq!(parse(f("%Y-%m-%d"), "2012/01/01"), None);
+        assert_eq!(parse(f("%Q"), "2012"), None);
+    }
+
+    #[test]
+    fn buckets_fill_gaps_in_order() {
+        let t = TimeTokenizer::new(&TimeFormat::Iso8601, 86400);
+        let c = io::Cursor::new("2012-01-03 10:00 b\n2012-01-01 08:00 a\nno timestamp\n2012-01-03 23:59 c\n");
+        assert_eq!(
+            t.tokenize(c),
+            vec![Pair::new(1, "2012-01-01"), Pair::new(0, "2012-01-02"), Pair::new(2, "2012-01-03")]
+        );
+    }
+
+    #[test]
+    fn outlier_leaves_out_empty_buckets() {
+        let t = TimeTokenizer::new(&TimeFormat::Epoch, 1);
+        assert_eq!(
+            t.tokenize(io::Cursor::new("5 x\n1700000000 y\n")),
+            vec![Pair::new(1, "1970-01-01 00:00:05"), Pair::new(1, "2023-11-14 22:13:20")]
+        );
+    }
+
+    #[test]
+    fn extreme_timestamps() {
+        let t = TimeTokenizer::new(&TimeFormat::Epoch, 3);
+        let (buckets, examined) = t.read_buckets(io::Cursor::new("-1e300\n1e300\n0\n"));
+        assert_eq!(examined, 3);
+        assert_eq!(buckets.len(), 2);
+        let (pairs, left_out) = t.pairs(&buckets);
+        assert_eq!(pairs.len(), 2);
+        assert!(left_out);
+    }
+
+    #[test]
+    fn bucket_labels() {
+        let hourly = TimeTokenizer::new(&TimeFormat::Epoch, 3600);
+        assert_eq!(
+            hourly.tokenize(io::Cursor::new("1325404912\n")),
+            vec![Pair::new(1, "2012-01-01 08:00")]
+        );
+
+        let seconds = TimeTokenizer::new(&TimeFormat::Epoch, 90);
+        assert_eq!(
+            seconds.tokenize(io::Cursor::new("1325404912\n")),
+            vec![Pair::new(1, "2012-01-01 08:01:30")]
+        );
+    }
+
+    #[test]
+    fn read_buckets() {
+        let t = TimeTokenizer::new(&TimeFormat::Epoch, 60);
+        let (buckets, examined) = t.read_buckets(io::Cursor::new("59\n60\n61\nx\n-1\n"));
+        assert_eq!(examined, 5);
+        assert_eq!(buckets.into_iter().collect::<Vec<_>>(), vec![(-60, 1), (0, 1), (60, 2)]);
+    }
+}
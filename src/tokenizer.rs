@@ -1,15 +1,111 @@
-use std::collections::HashMap;
 use std::io;
+use std::sync::mpsc;
+use std::thread;
 
 use super::pairlist::Pair;
+use super::summary::Summary;
 
+use indexmap::IndexMap;
 use log::debug;
 use regex::Regex;
+use rustc_hash::FxBuildHasher;
+
+pub type FxIndexMap<K, V> = IndexMap<K, V, FxBuildHasher>;
+
+// number of bytes of input handed to a worker thread at a time
+const CHUNK_SIZE: usize = 1 << 20;
 
 pub trait Tokenizer {
     fn tokenize<T: io::BufRead>(&self, reader: T) -> Vec<Pair>;
 }
 
+/// Tokenizers that reduce each line of input to zero or more keys to be counted.
+///
+/// Counting a line only depends on the line itself, so input can be split across threads and the
+/// per-thread tallies merged afterwards.
+pub trait LineCounter: Sync {
+    fn count_line<C: Tally>(&self, line: &str, tally: &mut C);
+
+    fn count<T: io::BufRead, C: Tally>(&self, reader: T, mut tally: C) -> C {
+        for_each_line(reader, |line| self.count_line(line, &mut tally));
+        tally
+    }
+}
+
+/// Running count of the keys produced by a `LineCounter`.
+pub trait Tally: Send {
+    /// Records that a token (or line) was read, whether or not it goes on to be counted.
+    fn examine(&mut self);
+
+    fn increment(&mut self, key: &str);
+
+    /// Adds the counts of another tally of the same kind, e.g. one kept by another thread.
+    fn merge(&mut self, other: Self);
+
+    fn summary(&self) -> Summary;
+
+    fn into_pairs(self) -> Vec<Pair>;
+}
+
+/// Exact tally of keys seen so far.
+///
+/// Keys are hashed with FxHash rather than the default SipHash, as it is several times faster on
+/// short keys. Keys can come from untrusted logs, and FxHash isn't seeded, so input crafted to
+/// collide could slow counting down; but this is a local command counting the input it is given,
+/// so that costs no more than the time of the one run reading it, and memory still only grows with
+/// the number of distinct keys.
+#[derive(Clone, Debug, Default)]
+pub struct Counts {
+    // kept in order of first appearance for --sort=input
+    counts: FxIndexMap<String, u64>,
+    examined: u64,
+}
+
+impl Tally for Counts {
+    fn examine(&mut self) {
+        self.examined += 1
+    }
+
+    // look the key up by reference first so only keys seen for the first time are allocated
+    fn increment(&mut self, key: &str) {
+        if let Some(value) = self.counts.get_mut(key) {
+            *value += 1
+        } else {
+            self.counts.insert(String::from(key), 1);
+        }
+    }
+
+    fn merge(&mut self, other: Counts) {
+        self.examined += other.examined;
+        for (key, value) in other.counts {
+            *self.counts.entry(key).or_default() += value;
+        }
+    }
+
+    fn summary(&self) -> Summary {
+        let matched = self.counts.values().sum();
+        Summary::new(self.examined, matched, self.counts.len() as u64)
+    }
+
+    fn into_pairs(self) -> Vec<Pair> {
+        let mut vec = Vec::with_capacity(self.counts.len());
+        for (key, &value) in &self.counts {
+            vec.push(Pair::new(value, key))
+        }
+        vec
+    }
+}
+
+/// Calls `f` with each line of input, without its line terminator, reusing a single buffer.
+pub fn for_each_line<T: io::BufRead, F: FnMut(&str)>(mut reader: T, mut f: F) {
+    let mut buf = String::new();
+    while reader.read_line(&mut buf).unwrap() > 0 {
+        let line = buf.strip_suffix('\n').unwrap_or(&buf);
+        f(line.strip_suffix('\r').unwrap_or(line));
+        buf.clear();
+    }
+}
+
 pub struct PreTalliedTokenizer {
     re: Regex,
 }
@@ -56,23 +152,18 @@ impl LineTokenizer {
     }
 }
 
-impl Tokenizer for LineTokenizer {
-    fn tokenize<T: io::BufRead>(&self, reader: T) -> Vec<Pair> {
-        let mut counts: HashMap<String, u64> = HashMap::new();
-
-        for line in reader.lines() {
-            let key = line.unwrap();
-            if self.re.is_match(key.as_str()) {
-                let value = counts.entry(key).or_insert(0);
-                *value += 1
-            }
+impl LineCounter for LineTokenizer {
+    fn count_line<C: Tally>(&self, line: &str, tally: &mut C) {
+        tally.examine();
+        if self.re.is_match(line) {
+            tally.increment(line)
         }
+    }
+}
 
-        let mut vec = Vec::new();
-        for (key, &value) in &counts {
-            vec.push(Pair::new(value, key))
-        }
-        vec
+impl Tokenizer for LineTokenizer {
+    fn tokenize<T: io::BufRead>(&self, reader: T) -> Vec<Pair> {
+        self.count(reader, Counts::default()).into_pairs()
     }
 }
 
@@ -102,33 +193,114 @@ impl RegexTokenizer {
     }
 }
 
+impl RegexTokenizer {
+    fn count_token<C: Tally>(&self, token: &str, tally: &mut C) {
+        tally.examine();
+        if self.matcher.is_match(token) {
+            tally.increment(token)
+        }
+    }
+}
+
+impl LineCounter for RegexTokenizer {
+    fn count_line<C: Tally>(&self, line: &str, tally: &mut C) {
+        for token in self.splitter.split(line.trim_end()) {
+            self.count_token(token, tally);
+        }
+    }
+}
+
 impl Tokenizer for RegexTokenizer {
     fn tokenize<T: io::BufRead>(&self, reader: T) -> Vec<Pair> {
-        let mut counts: HashMap<String, u64> = HashMap::new();
-
-        for l in reader.lines() {
-            let line = l.unwrap();
-            for token in self.splitter.split(line.trim_end()) {
-                if self.matcher.is_match(token) {
-                    let value = counts.entry(String::from(token)).or_insert(0);
-                    *value += 1
-                }
+        let vec = self.count(reader, Counts::default()).into_pairs();
+        debug!("[vec={:?}]", vec);
+        vec
+    }
+}
+
+/// Splits input into chunks of whole lines and counts them on a pool of worker threads.
+///
+/// Each worker keeps its own tally, which are merged once all input has been read, so exact counts
+/// are the same as counting on a single thread.
+pub struct ParallelTokenizer<L: LineCounter> {
+    counter: L,
+    threads: usize,
+}
+
+impl<L: LineCounter> ParallelTokenizer<L> {
+    pub fn new(counter: L, threads: usize) -> ParallelTokenizer<L> {
+        ParallelTokenizer { counter, threads }
+    }
+
+    /// Counts all of `reader` into `tally`, which must be empty as each worker starts from a copy
+    /// of it.
+    pub fn count<T: io::BufRead, C: Tally + Clone>(&self, mut reader: T, mut tally: C) -> C {
+        if self.threads <= 1 {
+            return self.counter.count(reader, tally);
+        }
+
+        thread::scope(|scope| {
+            let mut senders = Vec::new();
+            let mut workers = Vec::new();
+            for _ in 0..self.threads {
+                let (tx, rx) = mpsc::sync_channel::<String>(2);
+                let mut worker_tally = tally.clone();
+                senders.push(tx);
+                workers.push(scope.spawn(move || {
+                    for chunk in rx {
+                        for line in chunk.lines() {
+                            self.counter.count_line(line, &mut worker_tally);
+                        }
+                    }
+                    worker_tally
+                }));
+            }
+
+            // hand out chunks round-robin; a worker blocks the reader once it falls two chunks behind
+            let mut i = 0;
+            while let Some(chunk) = ParallelTokenizer::<L>::read_chunk(&mut reader) {
+                senders[i % self.threads].send(chunk).unwrap();
+                i += 1;
+            }
+            drop(senders);
+
+            for worker in workers {
+                tally.merge(worker.join().unwrap());
+            }
+        });
+
+        debug!("[threads={}]", self.threads);
+        tally
+    }
+
+    fn read_chunk<T: io::BufRead>(reader: &mut T) -> Option<String> {
+        let mut buf = Vec::with_capacity(CHUNK_SIZE);
+        while buf.len() < CHUNK_SIZE {
+            if reader.read_until(b'\n', &mut buf).unwrap() == 0 {
+                break;
             }
         }
 
-        let mut vec = Vec::new();
-        for (key, &value) in &counts {
-            vec.push(Pair::new(value, key))
+        if buf.is_empty() {
+            None
+        } else {
+            Some(String::from_utf8(buf).unwrap())
         }
-        debug!("[vec={:?}]", vec);
-        vec
+    }
+}
+
+impl<L: LineCounter> Tokenizer for ParallelTokenizer<L> {
+    fn tokenize<T: io::BufRead>(&self, reader: T) -> Vec<Pair> {
+        self.count(reader, Counts::default()).into_pairs()
     }
 }
 
 #[cfg(test)]
 mod test {
     use crate::pairlist::Pair;
-    use crate::tokenizer::{LineTokenizer, PreTalliedTokenizer, RegexTokenizer, Tokenizer};
+    use crate::summary::Summary;
+    use crate::tokenizer::{Counts, LineCounter, Tally};
+    use crate::tokenizer::{LineTokenizer, ParallelTokenizer, PreTalliedTokenizer, RegexTokenizer, Tokenizer};
     use std::io;
 
     #[test]
@@ -193,17 +365,35 @@ mod test {
         let c = io::Cursor::new(b"1 aa\n2 ab\n1 ba");
         let mut actual = t.tokenize(c);
 
-        actual.sort_by(|a, b| b.cmp(&a));
+        actual.sort_by(|a, b| b.cmp(a));
         assert_eq!(actual, vec![Pair::new(1, "2 ab"), Pair::new(1, "1 ba"), Pair::new(1, "1 aa")]);
     }
 
+    #[test]
+    fn line_tokenize_strips_line_endings() {
+        let t = LineTokenizer::new(r".");
+        let c = io::Cursor::new(b"a\r\na\nb");
+        let mut actual = t.tokenize(c);
+
+        actual.sort_by(|a, b| b.cmp(a));
+        assert_eq!(actual, vec![Pair::new(2, "a"), Pair::new(1, "b")]);
+    }
+
+    #[test]
+    fn line_tokenize_first_appearance_order() {
+        let t = LineTokenizer::new(r".");
+        let c = io::Cursor::new(b"c\na\nc\nb\na");
+
+        assert_eq!(t.tokenize(c), vec![Pair::new(2, "c"), Pair::new(2, "a"), Pair::new(1, "b")]);
+    }
+
     #[test]
     fn regex_tokenizer() {
         let t = RegexTokenizer::new(r"/", r".+");
         let c = io::Cursor::new("/var/log/apparmor\n/var/log/dmesg.1.gz");
         let mut actual = t.tokenize(c);
 
-        actual.sort_by(|a, b| b.cmp(&a));
+        actual.sort_by(|a, b| b.cmp(a));
         assert_eq!(
             actual,
             vec![
@@ -214,4 +404,59 @@ mod test {
             ]
         );
     }
+
+    #[test]
+    fn parallel_tokenize_empty_reader() {
+        let t = ParallelTokenizer::new(LineTokenizer::new(r"."), 4);
+        let c = io::Cursor::new(b"");
+        assert_eq!(t.tokenize(c), vec![]);
+    }
+
+    #[test]
+    fn parallel_line_tokenizer_matches_sequential() {
+        let input: String = (0..50_000).map(|i| format!("{}\n", (i * 7919) % 97)).collect();
+
+        let mut expected = LineTokenizer::new(r".").tokenize(io::Cursor::new(&input));
+        let mut actual = ParallelTokenizer::new(LineTokenizer::new(r"."), 3).tokenize(io::Cursor::new(&input));
+
+        expected.sort_by(|a, b| b.cmp(a));
+        actual.sort_by(|a, b| b.cmp(a));
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn parallel_regex_tokenizer() {
+        let t = ParallelTokenizer::new(RegexTokenizer::new(r"/", r".+"), 2);
+        let c = io::Cursor::new("/var/log/apparmor\n/var/log/dmesg.1.gz");
+        let mut actual = t.tokenize(c);
+
+        actual.sort_by(|a, b| b.cmp(a));
+        assert_eq!(
+            actual,
+            vec![
+                Pair::new(2, "var"),
+                Pair::new(2, "log"),
+                Pair::new(1, "dmesg.1.gz"),
+                Pair::new(1, "apparmor"),
+            ]
+        );
+    }
+
+    #[test]
+    fn line_counter_summary() {
+        let t = LineTokenizer::new(r"a");
+        let c = io::Cursor::new(b"a\nb\na\nab");
+        let tally = t.count(c, Counts::default());
+
+        assert_eq!(tally.summary(), Summary::new(4, 3, 2));
+    }
+
+    #[test]
+    fn regex_counter_summary() {
+        let t = RegexTokenizer::new(r"\s+", r"^\d+$");
+        let c = io::Cursor::new("1 a 2\nb 1");
+        let tally = ParallelTokenizer::new(t, 2).count(c, Counts::default());
+
+        assert_eq!(tally.summary(), Summary::new(5, 3, 2));
+    }
 }
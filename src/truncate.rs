@@ -0,0 +1,140 @@
+use std::borrow::Cow;
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use super::settings::Truncation;
+use super::width::{display_width, grapheme_width};
+
+const ELLIPSIS: char = '…';
+
+/// Shortens `key` to at most `width` columns, marking where characters were removed with an
+/// ellipsis. Keys that already fit are returned unchanged.
+///
+/// Whole grapheme clusters are kept or removed, so the result may be a column narrower than
+/// `width` when a wide character doesn't fit.
+pub fn truncate<'a>(key: &'a str, width: usize, mode: &Truncation) -> Cow<'a, str> {
+    if display_width(key) <= width {
+        return Cow::Borrowed(key);
+    }
+    let shortened = match mode {
+        Truncation::End => end(key, width),
+        Truncation::Middle => middle(key, width),
+        Truncation::Path => path(key, width),
+    };
+    Cow::Owned(shortened)
+}
+
+// the longest start of `key` at most `width` columns wide
+fn head(key: &str, width: usize) -> &str {
+    let mut used = 0;
+    for (i, g) in key.grapheme_indices(true) {
+        used += grapheme_width(g);
+        if used > width {
+            return &key[..i];
+        }
+    }
+    key
+}
+
+// the longest end of `key` at most `width` columns wide
+fn tail(key: &str, width: usize) -> &str {
+    let mut used = 0;
+    for (i, g) in key.grapheme_indices(true).rev() {
+        used += grapheme_width(g);
+        if used > width {
+            return &key[i + g.len()..];
+        }
+    }
+    key
+}
+
+fn end(key: &str, width: usize) -> String {
+    if width == 0 {
+        return String::new();
+    }
+    format!("{}{}", head(key, width - 1), ELLIPSIS)
+}
+
+fn middle(key: &str, width: usize) -> String {
+    if width == 0 {
+        return String::new();
+    }
+    let keep = width - 1;
+    format!("{}{}{}", head(key, keep - keep / 2), ELLIPSIS, tail(key, keep / 2))
+}
+
+// abbreviate leading directories to their first character, then drop them, keeping the file name
+fn path(key: &str, width: usize) -> String {
+    let mut parts: Vec<String> = key.split('/').map(String::from).collect();
+    let fits = |parts: &[String]| display_width(&parts.join("/")) <= width;
+
+    let last = parts.len() - 1;
+    for i in 0..last {
+        if let Some(g) = parts[i].graphemes(true).next() {
+            parts[i] = g.to_string();
+        }
+        if fits(&parts) {
+            return parts.join("/");
+        }
+    }
+
+    for i in 0..last {
+        let shortened = format!("{}/{}", ELLIPSIS, parts[i + 1..].join("/"));
+        if display_width(&shortened) <= width {
+            return shortened;
+        }
+    }
+
+    // even the file name is too long
+    format!("{}{}", ELLIPSIS, tail(&parts[last], width.saturating_sub(1)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::truncate;
+    use crate::settings::Truncation;
+
+    #[test]
+    fn fits() {
+        assert_eq!(truncate("short", 5, &Truncation::End), "short");
+        assert_eq!(truncate("/a/b", 10, &Truncation::Path), "/a/b");
+    }
+
+    #[test]
+    fn end() {
+        assert_eq!(truncate("abcdefghij", 5, &Truncation::End), "abcd…");
+        assert_eq!(truncate("abcdefghij", 1, &Truncation::End), "…");
+        assert_eq!(truncate("äöüäöü", 4, &Truncation::End), "äöü…");
+    }
+
+    #[test]
+    fn wide_characters() {
+        assert_eq!(truncate("日本語のキー", 7, &Truncation::End), "日本語…");
+        assert_eq!(truncate("日本語のキー", 6, &Truncation::End), "日本…");
+        assert_eq!(truncate("日本語のキー", 8, &Truncation::Middle), "日本…ー");
+        assert_eq!(truncate("/データ/ログ/今日.log", 15, &Truncation::Path), "/デ/ロ/今日.log");
+        // combining accents stay with their letter
+        assert_eq!(truncate("e\u{301}e\u{301}e\u{301}", 2, &Truncation::End), "e\u{301}…");
+    }
+
+    #[test]
+    fn middle() {
+        assert_eq!(truncate("abcdefghij", 5, &Truncation::Middle), "ab…ij");
+        assert_eq!(truncate("abcdefghij", 6, &Truncation::Middle), "abc…ij");
+        assert_eq!(
+            truncate("http://example.com/a/very/long/url", 16, &Truncation::Middle),
+            "http://e…ong/url"
+        );
+    }
+
+    #[test]
+    fn path() {
+        let key = "/usr/share/doc/distribution/README.md";
+        assert_eq!(truncate(key, 31, &Truncation::Path), "/u/s/doc/distribution/README.md");
+        assert_eq!(truncate(key, 29, &Truncation::Path), "/u/s/d/distribution/README.md");
+        assert_eq!(truncate(key, 20, &Truncation::Path), "/u/s/d/d/README.md");
+        assert_eq!(truncate(key, 13, &Truncation::Path), "…/d/README.md");
+        assert_eq!(truncate(key, 12, &Truncation::Path), "…/README.md");
+        assert_eq!(truncate(key, 6, &Truncation::Path), "…ME.md");
+    }
+}
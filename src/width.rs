@@ -0,0 +1,54 @@
+use std::cmp;
+
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+/// Number of terminal columns `s` takes up.
+///
+/// Counted per grapheme cluster, so a letter with combining accents is one column and East Asian
+/// wide characters and emoji (including sequences joined into one emoji) are two.
+pub fn display_width(s: &str) -> usize {
+    s.graphemes(true).map(grapheme_width).sum()
+}
+
+// a cluster is drawn as a single glyph, which is never wider than two columns
+pub fn grapheme_width(g: &str) -> usize {
+    cmp::min(g.width(), 2)
+}
+
+/// Right-aligns `s` in `width` columns, like `{:>width$}` but counting display width.
+pub fn pad_left(s: &str, width: usize) -> String {
+    format!("{}{}", " ".repeat(width.saturating_sub(display_width(s))), s)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{display_width, pad_left};
+
+    #[test]
+    fn ascii() {
+        assert_eq!(display_width(""), 0);
+        assert_eq!(display_width("key"), 3);
+    }
+
+    #[test]
+    fn combining_characters() {
+        // e followed by a combining acute accent
+        assert_eq!(display_width("caf\u{301}e"), 4);
+        assert_eq!(display_width("Ξαβ"), 3);
+    }
+
+    #[test]
+    fn wide_characters() {
+        assert_eq!(display_width("日本語"), 6);
+        assert_eq!(display_width("a🎉b"), 4);
+        // a family emoji joined into one glyph
+        assert_eq!(display_width("👨\u{200d}👩\u{200d}👧"), 2);
+    }
+
+    #[test]
+    fn padding() {
+        assert_eq!(pad_left("日本", 6), "  日本");
+        assert_eq!(pad_left("abc", 2), "abc");
+    }
+}